@@ -0,0 +1,50 @@
+extern crate cryptonote_raw_crypto;
+
+use cryptonote_raw_crypto::multiexp::MultiExp;
+use cryptonote_raw_crypto::scalar::{Point, Scalar};
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 10;
+
+fn measure<F: FnMut() -> Point>(mut f: F) -> Duration {
+  let start = Instant::now();
+  for _ in 0..ITERATIONS {
+    f();
+  }
+  start.elapsed() / ITERATIONS
+}
+
+// Compares sum(a_i * P_i + b_i * G) computed with one double scalar
+// multiplication per term against a single multi-scalar multiplication.
+fn main() {
+  let g = Point::generator();
+  for n in &[4, 16, 64, 128, 256, 1024] {
+    let a: Vec<Scalar> = (0..*n).map(|_| Scalar::random()).collect();
+    let b: Vec<Scalar> = (0..*n).map(|_| Scalar::random()).collect();
+    let points: Vec<Point> = (0..*n).map(|_| Point::mul_base(&Scalar::random())).collect();
+
+    let mut scalars = a.clone();
+    scalars.extend(b.iter().cloned());
+    let mut bases = points.clone();
+    bases.extend((0..*n).map(|_| g));
+
+    let repeated = measure(|| {
+      (0..*n).fold(Point::identity(), |acc, i| {
+        acc + Point::double_mul_base(&a[i], &points[i], &b[i])
+      })
+    });
+    let straus = measure(|| MultiExp::straus(&scalars, &bases));
+    let pippenger = measure(|| MultiExp::pippenger(&scalars, &bases));
+    let compute = measure(|| MultiExp::compute(&scalars, &bases));
+    println!(
+      "n = {:5}: double scalar mult {:>12?}, straus {:>12?} ({:.2}x), pippenger {:>12?} ({:.2}x), compute {:>12?}",
+      n,
+      repeated,
+      straus,
+      repeated.as_secs_f64() / straus.as_secs_f64(),
+      pippenger,
+      repeated.as_secs_f64() / pippenger.as_secs_f64(),
+      compute
+    );
+  }
+}
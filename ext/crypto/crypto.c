@@ -38,7 +38,7 @@ int check_public_key(const uint8_t *public_key)
 void random_scalar(uint8_t *res)
 {
   unsigned char tmp[64];
-  generate_random_bytes_not_thread_safe(64, tmp);
+  generate_random_bytes_thread_safe(64, tmp);
   sc_reduce(tmp);
   memcpy(res, tmp, 32);
 }
@@ -33,7 +33,6 @@
 #include <string.h>
 
 #include "hash-ops.h"
-#include "initializer.h"
 #include "random.h"
 
 static void generate_system_random_bytes(size_t n, void *result);
@@ -95,49 +94,33 @@ static void generate_system_random_bytes(size_t n, void *result) {
 
 #endif
 
-static union hash_state state;
-
-#if !defined(NDEBUG)
-static volatile int curstate; /* To catch thread safety problems. */
+/* Every thread keeps its own Keccak state, seeded from the system on first
+ * use, so concurrent callers never share or race on a state. */
+#if defined(_MSC_VER)
+#define THREAD_LOCAL __declspec(thread)
+#else
+#define THREAD_LOCAL __thread
 #endif
 
-FINALIZER(deinit_random) {
-#if !defined(NDEBUG)
-  assert(curstate == 1);
-  curstate = 0;
-#endif
-  memset(&state, 0, sizeof(union hash_state));
-}
+static THREAD_LOCAL union hash_state state;
+static THREAD_LOCAL int state_ready;
 
-INITIALIZER(init_random) {
-  generate_system_random_bytes(32, &state);
-  REGISTER_FINALIZER(deinit_random);
-#if !defined(NDEBUG)
-  assert(curstate == 0);
-  curstate = 1;
-#endif
+static void ensure_random_state(void) {
+  if (!state_ready) {
+    generate_system_random_bytes(32, &state);
+    state_ready = 1;
+  }
 }
 
-void generate_random_bytes_not_thread_safe(size_t n, void *result) {
-#if !defined(NDEBUG)
-  assert(curstate == 1);
-  curstate = 2;
-#endif
+void generate_random_bytes_thread_safe(size_t n, void *result) {
+  ensure_random_state();
   if (n == 0) {
-#if !defined(NDEBUG)
-    assert(curstate == 2);
-    curstate = 1;
-#endif
     return;
   }
   for (;;) {
     hash_permutation(&state);
     if (n <= HASH_DATA_AREA) {
       memcpy(result, &state, n);
-#if !defined(NDEBUG)
-      assert(curstate == 2);
-      curstate = 1;
-#endif
       return;
     } else {
       memcpy(result, &state, HASH_DATA_AREA);
@@ -147,7 +130,15 @@ void generate_random_bytes_not_thread_safe(size_t n, void *result) {
   }
 }
 
+/* Kept for existing callers, the state is per thread now. */
+void generate_random_bytes_not_thread_safe(size_t n, void *result) {
+  generate_random_bytes_thread_safe(n, result);
+}
+
+/* Replaces the state of the calling thread only, so that a test can replay
+ * the same random sequence without affecting other threads. */
 void setup_random(int value)
 {
   memset(&state, value, sizeof(union hash_state));
+  state_ready = 1;
 }
@@ -32,5 +32,6 @@
 
 #include <stddef.h>
 
+void generate_random_bytes_thread_safe(size_t n, void *result);
 void generate_random_bytes_not_thread_safe(size_t n, void *result);
 void setup_random(int value);
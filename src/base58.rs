@@ -0,0 +1,95 @@
+// CryptoNote base58: the input is cut into blocks of 8 bytes, each encoded
+// to exactly 11 characters, the last partial block to a fixed shorter size.
+const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const FULL_BLOCK_SIZE: usize = 8;
+const FULL_ENCODED_BLOCK_SIZE: usize = 11;
+const ENCODED_BLOCK_SIZES: [usize; 9] = [0, 2, 3, 5, 6, 7, 9, 10, 11];
+
+pub struct Base58 {}
+
+fn encode_block(block: &[u8], encoded: &mut Vec<u8>) {
+  let mut num: u64 = 0;
+  for byte in block {
+    num = (num << 8) | *byte as u64;
+  }
+  let size = ENCODED_BLOCK_SIZES[block.len()];
+  let mut chars = vec![ALPHABET[0]; size];
+  let mut i = size;
+  while num > 0 {
+    i -= 1;
+    chars[i] = ALPHABET[(num % 58) as usize];
+    num /= 58;
+  }
+  encoded.extend_from_slice(&chars);
+}
+
+fn decode_block(block: &[u8], size: usize, decoded: &mut Vec<u8>) -> Option<()> {
+  let mut num: u64 = 0;
+  for c in block {
+    let digit = ALPHABET.iter().position(|a| a == c)? as u64;
+    num = num.checked_mul(58)?.checked_add(digit)?;
+  }
+  if size < FULL_BLOCK_SIZE && num >> (8 * size) != 0 {
+    return None;
+  }
+  decoded.extend_from_slice(&num.to_be_bytes()[FULL_BLOCK_SIZE - size..]);
+  Some(())
+}
+
+impl Base58 {
+  pub fn encode(data: &[u8]) -> String {
+    let mut encoded: Vec<u8> = vec![];
+    for block in data.chunks(FULL_BLOCK_SIZE) {
+      encode_block(block, &mut encoded);
+    }
+    String::from_utf8(encoded).unwrap()
+  }
+
+  pub fn decode(encoded: &str) -> Option<Vec<u8>> {
+    let bytes = encoded.as_bytes();
+    let mut decoded: Vec<u8> = vec![];
+    for block in bytes.chunks(FULL_ENCODED_BLOCK_SIZE) {
+      let size = ENCODED_BLOCK_SIZES.iter().position(|s| *s == block.len())?;
+      decode_block(block, size, &mut decoded)?;
+    }
+    Some(decoded)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn should_encode_and_decode_blocks() {
+    let vectors = [
+      ("", ""),
+      ("00", "11"),
+      ("39", "1z"),
+      ("ff", "5Q"),
+      ("0000000000000000", "11111111111"),
+      ("ffffffffffffffff", "jpXCZedGfVQ"),
+      ("06156013762879f7", "22222222222"),
+      ("05e022ba374b2a00", "1z111111111"),
+      ("06156013762879f7ffffffffff", "22222222222VtB5VXc"),
+    ];
+    for (data, encoded) in vectors.iter() {
+      let data = hex::decode(data).unwrap();
+      assert!(Base58::encode(&data) == *encoded);
+      assert!(Base58::decode(encoded) == Some(data));
+    }
+  }
+
+  #[test]
+  fn should_reject_invalid_strings() {
+    // 0 and l are not in the alphabet
+    assert!(Base58::decode("10").is_none());
+    assert!(Base58::decode("1l").is_none());
+    // no block encodes to 1 or 4 characters
+    assert!(Base58::decode("1").is_none());
+    assert!(Base58::decode("1111").is_none());
+    // overflows a one byte block and a full block
+    assert!(Base58::decode("5R").is_none());
+    assert!(Base58::decode("zzzzzzzzzzz").is_none());
+  }
+}
@@ -2,6 +2,7 @@ extern crate bindgen;
 extern crate cc;
 
 fn main() {
+    println!("cargo:rerun-if-changed=ext");
     let mut build = cc::Build::new();
     let tool = build.get_compiler();
     if tool.is_like_clang() || tool.is_like_gnu() {
@@ -0,0 +1,733 @@
+use super::commitment::Commitment;
+use super::hash::Hash;
+use super::multiexp::MultiExp;
+use super::scalar::{Point, Scalar};
+use super::serialization::{read_varint, write_varint};
+use rand_core::{CryptoRng, RngCore};
+use std::sync::OnceLock;
+
+// Proves amounts in [0, 2^64) for up to 16 outputs at once.
+pub const BULLETPROOF_BITS: usize = 64;
+pub const BULLETPROOF_MAX_OUTPUTS: usize = 16;
+
+const GENERATOR_TAG: &[u8] = b"bulletproof";
+
+#[derive(Debug, PartialEq)]
+pub enum BulletProofError {
+  NoAmounts,
+  TooManyAmounts,
+  MaskCountMismatch,
+  InvalidEncoding,
+}
+
+// Points are stored multiplied by 1/8 and multiplied back by 8 when
+// verifying, which clears any torsion component a prover could add.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BulletProof {
+  pub v: Vec<Point>,
+  pub a: Point,
+  pub s: Point,
+  pub t1: Point,
+  pub t2: Point,
+  pub taux: Scalar,
+  pub mu: Scalar,
+  pub l: Vec<Point>,
+  pub r: Vec<Point>,
+  pub a_final: Scalar,
+  pub b_final: Scalar,
+  pub t: Scalar,
+}
+
+struct Generators {
+  g: Vec<Point>,
+  h: Vec<Point>,
+}
+
+fn exponent(base: &Point, index: u64) -> Point {
+  let mut data = base.to_bytes().to_vec();
+  data.extend_from_slice(GENERATOR_TAG);
+  write_varint(&mut data, index);
+  Point::hash_to_point(&Hash::fast(&data))
+}
+
+fn generators() -> &'static Generators {
+  static GENERATORS: OnceLock<Generators> = OnceLock::new();
+  GENERATORS.get_or_init(|| {
+    let h = Commitment::generator_h();
+    let size = BULLETPROOF_BITS * BULLETPROOF_MAX_OUTPUTS;
+    Generators {
+      g: (0..size).map(|i| exponent(&h, (i * 2 + 1) as u64)).collect(),
+      h: (0..size).map(|i| exponent(&h, (i * 2) as u64)).collect(),
+    }
+  })
+}
+
+fn inv_eight() -> Scalar {
+  Scalar::from(8u64).invert().unwrap()
+}
+
+fn powers(x: &Scalar, n: usize) -> Vec<Scalar> {
+  let mut result = Vec::with_capacity(n);
+  let mut current = Scalar::one();
+  for _ in 0..n {
+    result.push(current);
+    current = current * x;
+  }
+  result
+}
+
+fn inner_product(a: &[Scalar], b: &[Scalar]) -> Scalar {
+  a.iter().zip(b.iter()).fold(Scalar::zero(), |acc, (x, y)| x.mul_add(y, &acc))
+}
+
+fn hash_points(cache: &Scalar, points: &[&Point]) -> Scalar {
+  let mut data = cache.to_bytes().to_vec();
+  for point in points {
+    data.extend_from_slice(&point.to_bytes());
+  }
+  Scalar::hash(&data)
+}
+
+fn hash_scalars(cache: &Scalar, scalars: &[&Scalar]) -> Scalar {
+  let mut data = cache.to_bytes().to_vec();
+  for scalar in scalars {
+    data.extend_from_slice(scalar.as_bytes());
+  }
+  Scalar::hash(&data)
+}
+
+// x = H(z || z || T1 || T2): the reference mashes z into the cache, which
+// already is z.
+fn challenge_x(z: &Scalar, t1: &Point, t2: &Point) -> Scalar {
+  let mut data = z.to_bytes().to_vec();
+  data.extend_from_slice(z.as_bytes());
+  data.extend_from_slice(&t1.to_bytes());
+  data.extend_from_slice(&t2.to_bytes());
+  Scalar::hash(&data)
+}
+
+// Number of padded outputs, a power of two.
+fn padded_outputs(count: usize) -> usize {
+  count.next_power_of_two()
+}
+
+// z^(2 + j) * 2^(i mod 64) for the bit i of output j.
+fn zz2(z: &Scalar, outputs: usize) -> Vec<Scalar> {
+  let two_powers = powers(&Scalar::from(2u64), BULLETPROOF_BITS);
+  let mut result = Vec::with_capacity(outputs * BULLETPROOF_BITS);
+  let mut zj = z * z;
+  for _ in 0..outputs {
+    for p in &two_powers {
+      result.push(zj * p);
+    }
+    zj = zj * z;
+  }
+  result
+}
+
+// Products of the inner product challenges selected by the bits of i.
+fn challenge_products(w: &[Scalar], w_inv: &[Scalar], size: usize) -> Vec<Scalar> {
+  let rounds = w.len();
+  (0..size)
+    .map(|i| {
+      (0..rounds).fold(Scalar::one(), |acc, k| {
+        if (i >> (rounds - 1 - k)) & 1 == 1 {
+          acc * w[k]
+        } else {
+          acc * w_inv[k]
+        }
+      })
+    })
+    .collect()
+}
+
+impl BulletProof {
+  pub fn prove(amounts: &[u64], masks: &[Scalar]) -> Result<BulletProof, BulletProofError> {
+    BulletProof::prove_with_rng(amounts, masks, &mut rand::thread_rng())
+  }
+
+  pub fn prove_with_rng<R: RngCore + CryptoRng>(
+    amounts: &[u64],
+    masks: &[Scalar],
+    rng: &mut R,
+  ) -> Result<BulletProof, BulletProofError> {
+    if amounts.is_empty() {
+      return Err(BulletProofError::NoAmounts);
+    }
+    if amounts.len() > BULLETPROOF_MAX_OUTPUTS {
+      return Err(BulletProofError::TooManyAmounts);
+    }
+    if amounts.len() != masks.len() {
+      return Err(BulletProofError::MaskCountMismatch);
+    }
+    loop {
+      // A zero challenge restarts the proof with fresh randomness.
+      if let Some(proof) = BulletProof::try_prove(amounts, masks, rng) {
+        return Ok(proof);
+      }
+    }
+  }
+
+  fn try_prove<R: RngCore + CryptoRng>(amounts: &[u64], masks: &[Scalar], rng: &mut R) -> Option<BulletProof> {
+    let generators = generators();
+    let g = Point::generator();
+    let h = Commitment::generator_h();
+    let inv8 = inv_eight();
+    let outputs = padded_outputs(amounts.len());
+    let size = outputs * BULLETPROOF_BITS;
+    let gi = &generators.g[..size];
+    let hi = &generators.h[..size];
+
+    let v: Vec<Point> = amounts
+      .iter()
+      .zip(masks.iter())
+      .map(|(amount, mask)| Commitment::commit(mask, *amount).point * inv8)
+      .collect();
+
+    let mut a_l: Vec<Scalar> = Vec::with_capacity(size);
+    let mut a_r: Vec<Scalar> = Vec::with_capacity(size);
+    for j in 0..outputs {
+      let amount = if j < amounts.len() { amounts[j] } else { 0 };
+      for i in 0..BULLETPROOF_BITS {
+        let bit = Scalar::from((amount >> i) & 1);
+        a_l.push(bit);
+        a_r.push(bit - Scalar::one());
+      }
+    }
+
+    let alpha = Scalar::random_with_rng(rng);
+    let mut scalars = vec![alpha * inv8];
+    let mut points = vec![g];
+    scalars.extend(a_l.iter().map(|x| x * inv8));
+    points.extend_from_slice(gi);
+    scalars.extend(a_r.iter().map(|x| x * inv8));
+    points.extend_from_slice(hi);
+    let a = MultiExp::compute(&scalars, &points);
+
+    let s_l: Vec<Scalar> = (0..size).map(|_| Scalar::random_with_rng(rng)).collect();
+    let s_r: Vec<Scalar> = (0..size).map(|_| Scalar::random_with_rng(rng)).collect();
+    let rho = Scalar::random_with_rng(rng);
+    scalars[0] = rho * inv8;
+    for i in 0..size {
+      scalars[1 + i] = s_l[i] * inv8;
+      scalars[1 + size + i] = s_r[i] * inv8;
+    }
+    let s = MultiExp::compute(&scalars, &points);
+
+    let mut cache = BulletProof::initial_transcript(&v);
+    let y = hash_points(&cache, &[&a, &s]);
+    if y.is_zero() {
+      return None;
+    }
+    let z = Scalar::hash(y.as_bytes());
+    if z.is_zero() {
+      return None;
+    }
+    cache = z;
+
+    let y_powers = powers(&y, size);
+    let zz2 = zz2(&z, outputs);
+    let l0: Vec<Scalar> = a_l.iter().map(|x| x - z).collect();
+    let l1 = s_l;
+    let r0: Vec<Scalar> = (0..size).map(|i| y_powers[i].mul_add(&(a_r[i] + z), &zz2[i])).collect();
+    let r1: Vec<Scalar> = (0..size).map(|i| y_powers[i] * s_r[i]).collect();
+    let t1 = inner_product(&l0, &r1) + inner_product(&l1, &r0);
+    let t2 = inner_product(&l1, &r1);
+
+    let tau1 = Scalar::random_with_rng(rng);
+    let tau2 = Scalar::random_with_rng(rng);
+    let t1_point = (Point::mul_base(&tau1) + t1 * h) * inv8;
+    let t2_point = (Point::mul_base(&tau2) + t2 * h) * inv8;
+
+    let x = challenge_x(&cache, &t1_point, &t2_point);
+    if x.is_zero() {
+      return None;
+    }
+    cache = x;
+
+    let mut taux = tau2 * x * x + tau1 * x;
+    let mut zj = z * z;
+    for mask in masks {
+      taux = zj.mul_add(mask, &taux);
+      zj = zj * z;
+    }
+    let mu = rho.mul_add(&x, &alpha);
+
+    let mut l: Vec<Scalar> = (0..size).map(|i| l1[i].mul_add(&x, &l0[i])).collect();
+    let mut r: Vec<Scalar> = (0..size).map(|i| r1[i].mul_add(&x, &r0[i])).collect();
+    let t = inner_product(&l, &r);
+
+    let x_ip = hash_scalars(&cache, &[&x, &taux, &mu, &t]);
+    if x_ip.is_zero() {
+      return None;
+    }
+    cache = x_ip;
+    let u = x_ip * h;
+
+    let y_inv = y.invert()?;
+    let y_inv_powers = powers(&y_inv, size);
+    let mut g_prime: Vec<Point> = gi.to_vec();
+    let mut h_prime: Vec<Point> = (0..size).map(|i| hi[i] * y_inv_powers[i]).collect();
+
+    let mut l_points: Vec<Point> = vec![];
+    let mut r_points: Vec<Point> = vec![];
+    let mut n = size;
+    while n > 1 {
+      n /= 2;
+      let c_l = inner_product(&l[..n], &r[n..]);
+      let c_r = inner_product(&l[n..], &r[..n]);
+
+      let mut scalars: Vec<Scalar> = l[..n].iter().map(|x| x * inv8).collect();
+      scalars.extend(r[n..].iter().map(|x| x * inv8));
+      scalars.push(c_l * inv8);
+      let mut points: Vec<Point> = g_prime[n..].to_vec();
+      points.extend_from_slice(&h_prime[..n]);
+      points.push(u);
+      let l_point = MultiExp::compute(&scalars, &points);
+
+      let mut scalars: Vec<Scalar> = l[n..].iter().map(|x| x * inv8).collect();
+      scalars.extend(r[..n].iter().map(|x| x * inv8));
+      scalars.push(c_r * inv8);
+      let mut points: Vec<Point> = g_prime[..n].to_vec();
+      points.extend_from_slice(&h_prime[n..]);
+      points.push(u);
+      let r_point = MultiExp::compute(&scalars, &points);
+
+      let w = hash_points(&cache, &[&l_point, &r_point]);
+      if w.is_zero() {
+        return None;
+      }
+      cache = w;
+      let w_inv = w.invert()?;
+
+      for i in 0..n {
+        g_prime[i] = MultiExp::straus(&[w_inv, w], &[g_prime[i], g_prime[i + n]]);
+        h_prime[i] = MultiExp::straus(&[w, w_inv], &[h_prime[i], h_prime[i + n]]);
+        l[i] = l[i] * w + l[i + n] * w_inv;
+        r[i] = r[i] * w_inv + r[i + n] * w;
+      }
+      g_prime.truncate(n);
+      h_prime.truncate(n);
+      l.truncate(n);
+      r.truncate(n);
+      l_points.push(l_point);
+      r_points.push(r_point);
+    }
+
+    Some(BulletProof {
+      v,
+      a,
+      s,
+      t1: t1_point,
+      t2: t2_point,
+      taux,
+      mu,
+      l: l_points,
+      r: r_points,
+      a_final: l[0],
+      b_final: r[0],
+      t,
+    })
+  }
+
+  fn initial_transcript(v: &[Point]) -> Scalar {
+    let mut data: Vec<u8> = vec![];
+    for point in v {
+      data.extend_from_slice(&point.to_bytes());
+    }
+    Scalar::hash(&data)
+  }
+
+  // The commitments C = mask * G + amount * H this proof is about.
+  pub fn commitments(&self) -> Vec<Commitment> {
+    self
+      .v
+      .iter()
+      .map(|v| Commitment {
+        point: v.mul_by_pow_2(3),
+      })
+      .collect()
+  }
+
+  pub fn verify(&self) -> bool {
+    BulletProof::verify_batch(&[self])
+  }
+
+  // Checks all proofs with a single multi-scalar multiplication, each proof
+  // being weighted by random scalars so that they cannot cancel out.
+  pub fn verify_batch(proofs: &[&BulletProof]) -> bool {
+    BulletProof::verify_batch_with_rng(proofs, &mut rand::thread_rng())
+  }
+
+  pub fn verify_batch_with_rng<R: RngCore + CryptoRng>(proofs: &[&BulletProof], rng: &mut R) -> bool {
+    let generators = generators();
+    let max_size = proofs
+      .iter()
+      .map(|p| padded_outputs(p.v.len()) * BULLETPROOF_BITS)
+      .max()
+      .unwrap_or(0);
+    let mut gi_scalars = vec![Scalar::zero(); max_size];
+    let mut hi_scalars = vec![Scalar::zero(); max_size];
+    let mut g_scalar = Scalar::zero();
+    let mut h_scalar = Scalar::zero();
+    let mut scalars: Vec<Scalar> = vec![];
+    let mut points: Vec<Point> = vec![];
+    let eight = Scalar::from(8u64);
+
+    for proof in proofs {
+      if proof.v.is_empty() || proof.v.len() > BULLETPROOF_MAX_OUTPUTS {
+        return false;
+      }
+      let outputs = padded_outputs(proof.v.len());
+      let size = outputs * BULLETPROOF_BITS;
+      let rounds = size.trailing_zeros() as usize;
+      if proof.l.len() != rounds || proof.r.len() != rounds {
+        return false;
+      }
+
+      let mut cache = BulletProof::initial_transcript(&proof.v);
+      let y = hash_points(&cache, &[&proof.a, &proof.s]);
+      let z = Scalar::hash(y.as_bytes());
+      cache = z;
+      let x = challenge_x(&cache, &proof.t1, &proof.t2);
+      cache = x;
+      let x_ip = hash_scalars(&cache, &[&x, &proof.taux, &proof.mu, &proof.t]);
+      cache = x_ip;
+      let mut w: Vec<Scalar> = Vec::with_capacity(rounds);
+      for k in 0..rounds {
+        let challenge = hash_points(&cache, &[&proof.l[k], &proof.r[k]]);
+        cache = challenge;
+        w.push(challenge);
+      }
+      if y.is_zero() || z.is_zero() || x.is_zero() || x_ip.is_zero() || w.iter().any(|c| c.is_zero()) {
+        return false;
+      }
+      let w_inv: Vec<Scalar> = w.iter().map(|c| c.invert().unwrap()).collect();
+      let y_inv = y.invert().unwrap();
+
+      let beta = Scalar::random_with_rng(rng);
+      let gamma = Scalar::random_with_rng(rng);
+
+      // beta * (sum(z^(2 + j) * V_j) + x * T1 + x^2 * T2 + (delta - t) * H - taux * G) == 0
+      let y_powers = powers(&y, size);
+      let y_sum = y_powers.iter().fold(Scalar::zero(), |acc, p| acc + p);
+      let two_sum = Scalar::from(u64::MAX);
+      let z2 = z * z;
+      let mut delta = (z - z2) * y_sum;
+      let mut zj = z2 * z;
+      for _ in 0..outputs {
+        delta = delta - zj * two_sum;
+        zj = zj * z;
+      }
+      let beta8 = beta * eight;
+      let mut zj = z2;
+      for v in &proof.v {
+        scalars.push(beta8 * zj);
+        points.push(*v);
+        zj = zj * z;
+      }
+      scalars.push(beta8 * x);
+      points.push(proof.t1);
+      scalars.push(beta8 * x * x);
+      points.push(proof.t2);
+      h_scalar = h_scalar + beta * (delta - proof.t);
+      g_scalar = g_scalar - beta * proof.taux;
+
+      // gamma * (A + x * S - mu * G + <-z - a * s, Gi> + <z + y^-i * (zz2 - b / s), Hi>
+      //   + x_ip * (t - a * b) * H + sum(w^2 * L + w^-2 * R)) == 0
+      let gamma8 = gamma * eight;
+      scalars.push(gamma8);
+      points.push(proof.a);
+      scalars.push(gamma8 * x);
+      points.push(proof.s);
+      for k in 0..rounds {
+        scalars.push(gamma8 * w[k] * w[k]);
+        points.push(proof.l[k]);
+        scalars.push(gamma8 * w_inv[k] * w_inv[k]);
+        points.push(proof.r[k]);
+      }
+      g_scalar = g_scalar - gamma * proof.mu;
+      h_scalar = h_scalar + gamma * x_ip * (proof.t - proof.a_final * proof.b_final);
+
+      let s = challenge_products(&w, &w_inv, size);
+      let s_inv = challenge_products(&w_inv, &w, size);
+      let zz2 = zz2(&z, outputs);
+      let mut y_inv_power = Scalar::one();
+      for i in 0..size {
+        gi_scalars[i] = gi_scalars[i] - gamma * proof.a_final.mul_add(&s[i], &z);
+        let h_coefficient = z + y_inv_power * (zz2[i] - proof.b_final * s_inv[i]);
+        hi_scalars[i] = hi_scalars[i] + gamma * h_coefficient;
+        y_inv_power = y_inv_power * y_inv;
+      }
+    }
+
+    scalars.push(g_scalar);
+    points.push(Point::generator());
+    scalars.push(h_scalar);
+    points.push(Commitment::generator_h());
+    scalars.extend(gi_scalars);
+    points.extend_from_slice(&generators.g[..max_size]);
+    scalars.extend(hi_scalars);
+    points.extend_from_slice(&generators.h[..max_size]);
+    MultiExp::compute(&scalars, &points).is_identity()
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut data: Vec<u8> = vec![];
+    write_varint(&mut data, self.v.len() as u64);
+    for v in &self.v {
+      data.extend_from_slice(&v.to_bytes());
+    }
+    for point in &[self.a, self.s, self.t1, self.t2] {
+      data.extend_from_slice(&point.to_bytes());
+    }
+    data.extend_from_slice(self.taux.as_bytes());
+    data.extend_from_slice(self.mu.as_bytes());
+    write_varint(&mut data, self.l.len() as u64);
+    for point in self.l.iter().chain(self.r.iter()) {
+      data.extend_from_slice(&point.to_bytes());
+    }
+    data.extend_from_slice(self.a_final.as_bytes());
+    data.extend_from_slice(self.b_final.as_bytes());
+    data.extend_from_slice(self.t.as_bytes());
+    data
+  }
+
+  pub fn from_bytes(data: &[u8]) -> Result<BulletProof, BulletProofError> {
+    let mut reader = Reader { data, offset: 0 };
+    let count = reader.count(BULLETPROOF_MAX_OUTPUTS)?;
+    let mut v = Vec::with_capacity(count);
+    for _ in 0..count {
+      v.push(reader.point()?);
+    }
+    let a = reader.point()?;
+    let s = reader.point()?;
+    let t1 = reader.point()?;
+    let t2 = reader.point()?;
+    let taux = reader.scalar()?;
+    let mu = reader.scalar()?;
+    let rounds = reader.count(BULLETPROOF_BITS)?;
+    let mut l = Vec::with_capacity(rounds);
+    for _ in 0..rounds {
+      l.push(reader.point()?);
+    }
+    let mut r = Vec::with_capacity(rounds);
+    for _ in 0..rounds {
+      r.push(reader.point()?);
+    }
+    let a_final = reader.scalar()?;
+    let b_final = reader.scalar()?;
+    let t = reader.scalar()?;
+    if reader.offset != data.len() {
+      return Err(BulletProofError::InvalidEncoding);
+    }
+    Ok(BulletProof {
+      v,
+      a,
+      s,
+      t1,
+      t2,
+      taux,
+      mu,
+      l,
+      r,
+      a_final,
+      b_final,
+      t,
+    })
+  }
+}
+
+struct Reader<'a> {
+  data: &'a [u8],
+  offset: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn bytes(&mut self) -> Result<[u8; 32], BulletProofError> {
+    if self.offset + 32 > self.data.len() {
+      return Err(BulletProofError::InvalidEncoding);
+    }
+    let mut bytes: [u8; 32] = [0; 32];
+    bytes.copy_from_slice(&self.data[self.offset..self.offset + 32]);
+    self.offset += 32;
+    Ok(bytes)
+  }
+
+  fn point(&mut self) -> Result<Point, BulletProofError> {
+    Point::from_bytes(&self.bytes()?).ok_or(BulletProofError::InvalidEncoding)
+  }
+
+  fn scalar(&mut self) -> Result<Scalar, BulletProofError> {
+    Scalar::from_canonical_bytes(&self.bytes()?).ok_or(BulletProofError::InvalidEncoding)
+  }
+
+  fn count(&mut self, max: usize) -> Result<usize, BulletProofError> {
+    let value = read_varint(self.data, &mut self.offset).map_err(|_| BulletProofError::InvalidEncoding)?;
+    if value > max as u64 {
+      return Err(BulletProofError::InvalidEncoding);
+    }
+    Ok(value as usize)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs::{canonicalize, File};
+  use std::io::{prelude::*, BufReader};
+  use std::path::PathBuf;
+
+  // The verify_transaction_proof vectors are the range proofs of the mainnet
+  // transactions 4c9c4f6b3d298c5ffa31139929b34ae53d727adf3de70ed2b8c32835d4513e25
+  // and 5a420317e377d3d95b652fb93e65cfe97ef7d89e04be329a2ca94e73ec57b74e, the
+  // last one checked against the commitments of the other.
+  #[test]
+  fn should_match_known_answers() {
+    let path = PathBuf::from("./tests/bulletproof.txt");
+    let f = File::open(canonicalize(path).unwrap()).unwrap();
+    let file = BufReader::new(&f);
+    for line in file.lines() {
+      let l = line.unwrap();
+      let split: Vec<&str> = l.split_whitespace().collect();
+      match split[0] {
+        "generator_g" | "generator_h" => {
+          let index = split[1].parse::<usize>().unwrap();
+          let expected = hex::decode(split[2]).expect("Error parse generator");
+          let generators = generators();
+          let actual = if split[0] == "generator_g" {
+            generators.g[index]
+          } else {
+            generators.h[index]
+          };
+          assert!(actual.to_bytes() == expected.as_slice());
+        }
+        "verify_transaction_proof" => {
+          // The proof as serialized in an RCTTypeBulletproof2 transaction,
+          // which leaves V out: it is the output commitments times 1/8.
+          let count = split[1].parse::<usize>().unwrap();
+          let v: Vec<Point> = split[2..2 + count]
+            .iter()
+            .map(|commitment| {
+              let bytes = hex::decode(commitment).expect("Error parse commitment");
+              let mut fixed: [u8; 32] = [0; 32];
+              fixed.copy_from_slice(&bytes);
+              Point::from_bytes(&fixed).unwrap() * inv_eight()
+            })
+            .collect();
+          let bytes = hex::decode(split[2 + count]).expect("Error parse proof");
+          let expected = split[3 + count] == "true";
+          let mut reader = Reader {
+            data: &bytes,
+            offset: 0,
+          };
+          let (a, s, t1, t2) = (
+            reader.point().unwrap(),
+            reader.point().unwrap(),
+            reader.point().unwrap(),
+            reader.point().unwrap(),
+          );
+          let (taux, mu) = (reader.scalar().unwrap(), reader.scalar().unwrap());
+          let rounds = reader.count(BULLETPROOF_BITS).unwrap();
+          let l: Vec<Point> = (0..rounds).map(|_| reader.point().unwrap()).collect();
+          assert!(reader.count(BULLETPROOF_BITS).unwrap() == rounds);
+          let r: Vec<Point> = (0..rounds).map(|_| reader.point().unwrap()).collect();
+          let (a_final, b_final, t) = (
+            reader.scalar().unwrap(),
+            reader.scalar().unwrap(),
+            reader.scalar().unwrap(),
+          );
+          assert!(reader.offset == bytes.len());
+          let proof = BulletProof {
+            v,
+            a,
+            s,
+            t1,
+            t2,
+            taux,
+            mu,
+            l,
+            r,
+            a_final,
+            b_final,
+            t,
+          };
+          assert!(proof.verify() == expected);
+          assert!(BulletProof::from_bytes(&proof.to_bytes()) == Ok(proof));
+        }
+        _ => {}
+      }
+    }
+  }
+
+  #[test]
+  fn should_prove_single_amount() {
+    let mask = Scalar::random();
+    let proof = BulletProof::prove(&[123_456_789], &[mask]).unwrap();
+    assert!(proof.l.len() == 6);
+    assert!(proof.verify());
+    assert!(proof.commitments()[0] == Commitment::commit(&mask, 123_456_789));
+
+    let proof = BulletProof::prove(&[u64::MAX], &[mask]).unwrap();
+    assert!(proof.verify());
+    let proof = BulletProof::prove(&[0], &[mask]).unwrap();
+    assert!(proof.verify());
+  }
+
+  #[test]
+  fn should_prove_aggregated_amounts() {
+    let masks: Vec<Scalar> = (0..3).map(|_| Scalar::random()).collect();
+    let amounts = [1, 2_000_000_000, 30];
+    let proof = BulletProof::prove(&amounts, &masks).unwrap();
+    assert!(proof.l.len() == 8);
+    assert!(proof.verify());
+    for (i, commitment) in proof.commitments().iter().enumerate() {
+      assert!(*commitment == Commitment::commit(&masks[i], amounts[i]));
+    }
+  }
+
+  #[test]
+  fn should_reject_tampered_proofs() {
+    let proof = BulletProof::prove(&[5, 7], &[Scalar::random(), Scalar::random()]).unwrap();
+    let mut tampered = proof.clone();
+    tampered.t = tampered.t + Scalar::one();
+    assert!(!tampered.verify());
+
+    let mut tampered = proof.clone();
+    tampered.v[0] = tampered.v[0] + Point::generator();
+    assert!(!tampered.verify());
+
+    let mut tampered = proof.clone();
+    tampered.v.swap(0, 1);
+    assert!(!tampered.verify());
+
+    let mut tampered = proof.clone();
+    tampered.l.pop();
+    assert!(!tampered.verify());
+
+    assert!(BulletProof::prove(&[], &[]).err() == Some(BulletProofError::NoAmounts));
+    assert!(BulletProof::prove(&[1], &[]).err() == Some(BulletProofError::MaskCountMismatch));
+    assert!(BulletProof::prove(&[1; 17], &[Scalar::one(); 17]).err() == Some(BulletProofError::TooManyAmounts));
+  }
+
+  #[test]
+  fn should_verify_batch() {
+    let first = BulletProof::prove(&[10], &[Scalar::random()]).unwrap();
+    let second = BulletProof::prove(&[20, 30], &[Scalar::random(), Scalar::random()]).unwrap();
+    assert!(BulletProof::verify_batch(&[&first, &second]));
+    let mut tampered = second.clone();
+    tampered.mu = tampered.mu + Scalar::one();
+    assert!(!BulletProof::verify_batch(&[&first, &tampered]));
+    assert!(BulletProof::verify_batch(&[]));
+  }
+
+  #[test]
+  fn should_serialize_proofs() {
+    let proof = BulletProof::prove(&[42], &[Scalar::random()]).unwrap();
+    let bytes = proof.to_bytes();
+    assert!(bytes.len() == 1 + 32 + 4 * 32 + 2 * 32 + 1 + 12 * 32 + 3 * 32);
+    assert!(BulletProof::from_bytes(&bytes).unwrap() == proof);
+    assert!(BulletProof::from_bytes(&bytes[1..]).is_err());
+    assert!(BulletProof::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+  }
+}
@@ -1,7 +1,7 @@
 
 use super::consts::*;
 use super::hash::Hash;
-use rand::Rng;
+use rand_core::{CryptoRng, RngCore};
 
 extern "C" {
   fn chacha8(data: *const u8, length: usize, key: *const u8, iv: *const u8, cipher: *mut u8);
@@ -22,11 +22,11 @@ pub struct Chacha {
 
 impl ChachaIV {
   pub fn new() -> ChachaIV {
-    let mut rng = rand::thread_rng();
+    ChachaIV::new_with_rng(&mut rand::thread_rng())
+  }
+  pub fn new_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> ChachaIV {
     let mut data: [u8; CHACHA_IV_SIZE] = [0; CHACHA_IV_SIZE];
-    for x in &mut data {
-      *x = rng.gen();
-    }
+    rng.fill_bytes(&mut data);
     ChachaIV { data }
   }
   pub fn from(data: [u8; CHACHA_IV_SIZE]) -> ChachaIV {
@@ -67,6 +67,8 @@ impl ChachaKey {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
 
   extern crate hex;
 
@@ -95,6 +97,8 @@ mod tests {
     );
 
     let _iv = ChachaIV::new();
+    let seeded = ChachaIV::new_with_rng(&mut StdRng::seed_from_u64(7));
+    assert!(seeded.data == ChachaIV::new_with_rng(&mut StdRng::seed_from_u64(7)).data);
     let iv = ChachaIV::from([0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18]);
     let chacha = Chacha::new(key, iv);
     let plain = *b"hello world!";
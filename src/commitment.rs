@@ -0,0 +1,191 @@
+use super::hash::Hash;
+use super::scalar::{Point, Scalar};
+use std::ops::{Add, Sub};
+use std::sync::OnceLock;
+
+const COMMITMENT_MASK_TAG: &[u8] = b"commitment_mask";
+const AMOUNT_TAG: &[u8] = b"amount";
+
+// C = mask * G + amount * H
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Commitment {
+  pub point: Point,
+}
+
+impl Commitment {
+  // H = 8 * cn_fast_hash(G) read as a point, as in RingCT, so nobody knows
+  // log_G(H). The hash happens to decode as a point, unlike the general
+  // hash_to_point mapping which RingCT does not use for H. Computed once.
+  pub fn generator_h() -> Point {
+    static GENERATOR_H: OnceLock<Point> = OnceLock::new();
+    *GENERATOR_H.get_or_init(|| {
+      let hash = Hash::fast(&Point::generator().to_bytes());
+      Point::from_bytes(&hash).unwrap().mul_by_pow_2(3)
+    })
+  }
+
+  pub fn commit(mask: &Scalar, amount: u64) -> Commitment {
+    let point = Point::mul_base(mask) + Scalar::from(amount) * Commitment::generator_h();
+    Commitment { point }
+  }
+
+  // Commitment with a mask of zero, used for fees and cleartext amounts.
+  pub fn zero_mask(amount: u64) -> Commitment {
+    Commitment::commit(&Scalar::zero(), amount)
+  }
+
+  pub fn from_bytes(bytes: &[u8; 32]) -> Option<Commitment> {
+    Some(Commitment {
+      point: Point::from_bytes(bytes)?,
+    })
+  }
+
+  pub fn to_bytes(&self) -> [u8; 32] {
+    self.point.to_bytes()
+  }
+
+  pub fn sum(commitments: &[Commitment]) -> Commitment {
+    let point = commitments.iter().fold(Point::identity(), |acc, c| acc + c.point);
+    Commitment { point }
+  }
+
+  // sum(inputs) - sum(outputs) - fee * H == 0
+  pub fn is_balanced(inputs: &[Commitment], outputs: &[Commitment], fee: u64) -> bool {
+    let difference = Commitment::sum(inputs) - Commitment::sum(outputs) - Commitment::zero_mask(fee);
+    difference.point.is_identity()
+  }
+
+  // The mask of the last input that makes the input masks sum to the
+  // output masks, so that the commitments balance.
+  pub fn balancing_mask(input_masks: &[Scalar], output_masks: &[Scalar]) -> Scalar {
+    let outputs = output_masks.iter().fold(Scalar::zero(), |acc, m| acc + m);
+    let inputs = input_masks.iter().fold(Scalar::zero(), |acc, m| acc + m);
+    outputs - inputs
+  }
+
+  // The output mask derived from the shared secret H_s(derivation || index).
+  pub fn mask_from_secret(shared_secret: &[u8; 32]) -> Scalar {
+    let mut data = COMMITMENT_MASK_TAG.to_vec();
+    data.extend_from_slice(shared_secret);
+    Scalar::hash(&data)
+  }
+
+  fn amount_key(shared_secret: &[u8; 32]) -> [u8; 8] {
+    let mut data = AMOUNT_TAG.to_vec();
+    data.extend_from_slice(shared_secret);
+    let hash = Hash::fast(&data);
+    let mut key: [u8; 8] = [0; 8];
+    key.copy_from_slice(&hash[..8]);
+    key
+  }
+
+  pub fn encrypt_amount(amount: u64, shared_secret: &[u8; 32]) -> [u8; 8] {
+    let key = Commitment::amount_key(shared_secret);
+    let mut encrypted = amount.to_le_bytes();
+    for (byte, k) in encrypted.iter_mut().zip(key.iter()) {
+      *byte ^= k;
+    }
+    encrypted
+  }
+
+  pub fn decrypt_amount(encrypted: &[u8; 8], shared_secret: &[u8; 32]) -> u64 {
+    let key = Commitment::amount_key(shared_secret);
+    let mut amount: [u8; 8] = *encrypted;
+    for (byte, k) in amount.iter_mut().zip(key.iter()) {
+      *byte ^= k;
+    }
+    u64::from_le_bytes(amount)
+  }
+
+  // Recomputes the commitment of a received output to check the decrypted
+  // amount against the one in the transaction.
+  pub fn open(&self, shared_secret: &[u8; 32], encrypted: &[u8; 8]) -> Option<u64> {
+    let amount = Commitment::decrypt_amount(encrypted, shared_secret);
+    let mask = Commitment::mask_from_secret(shared_secret);
+    if Commitment::commit(&mask, amount) == *self {
+      Some(amount)
+    } else {
+      None
+    }
+  }
+}
+
+impl Add for Commitment {
+  type Output = Commitment;
+  fn add(self, other: Commitment) -> Commitment {
+    Commitment {
+      point: self.point + other.point,
+    }
+  }
+}
+
+impl Sub for Commitment {
+  type Output = Commitment;
+  fn sub(self, other: Commitment) -> Commitment {
+    Commitment {
+      point: self.point - other.point,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::key::Key;
+  use super::*;
+
+  #[test]
+  fn should_derive_generator_h() {
+    let h = Commitment::generator_h().to_bytes();
+    assert!(hex::encode(h) == "8b655970153799af2aeadc9ff1add0ea6c7251d54154cfa92c173a0dd39c1f94");
+  }
+
+  #[test]
+  fn should_add_and_subtract_commitments() {
+    let a = Scalar::hash(b"a");
+    let b = Scalar::hash(b"b");
+    let sum = Commitment::commit(&a, 10) + Commitment::commit(&b, 32);
+    assert!(sum == Commitment::commit(&(a + b), 42));
+    assert!(sum - Commitment::commit(&b, 32) == Commitment::commit(&a, 10));
+    assert!(Commitment::from_bytes(&sum.to_bytes()) == Some(sum));
+    assert!(Commitment::commit(&a, 10) != Commitment::commit(&a, 11));
+  }
+
+  #[test]
+  fn should_check_balance() {
+    let output_masks = vec![Scalar::random(), Scalar::random()];
+    let mut input_masks = vec![Scalar::random()];
+    input_masks.push(Commitment::balancing_mask(&input_masks, &output_masks));
+
+    let inputs = vec![
+      Commitment::commit(&input_masks[0], 600),
+      Commitment::commit(&input_masks[1], 500),
+    ];
+    let outputs = vec![
+      Commitment::commit(&output_masks[0], 700),
+      Commitment::commit(&output_masks[1], 390),
+    ];
+    assert!(Commitment::is_balanced(&inputs, &outputs, 10));
+    assert!(!Commitment::is_balanced(&inputs, &outputs, 9));
+    assert!(!Commitment::is_balanced(&inputs, &outputs[..1], 10));
+  }
+
+  #[test]
+  fn should_hide_amounts_with_shared_secret() {
+    let mut view_public: [u8; 32] = [0; 32];
+    let mut view_secret: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut view_public, &mut view_secret);
+    let mut tx_public: [u8; 32] = [0; 32];
+    let mut tx_secret: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut tx_public, &mut tx_secret);
+
+    let sender = Key::derive_secret_key(&Key::generate_key_derivation(&view_public, &tx_secret), 0, &[0; 32]);
+    let receiver = Key::derive_secret_key(&Key::generate_key_derivation(&tx_public, &view_secret), 0, &[0; 32]);
+    assert!(sender == receiver);
+
+    let commitment = Commitment::commit(&Commitment::mask_from_secret(&sender), 123_456_789);
+    let encrypted = Commitment::encrypt_amount(123_456_789, &sender);
+    assert!(Commitment::decrypt_amount(&encrypted, &receiver) == 123_456_789);
+    assert!(commitment.open(&receiver, &encrypted) == Some(123_456_789));
+    assert!(commitment.open(&[1; 32], &encrypted).is_none());
+  }
+}
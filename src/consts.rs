@@ -1,2 +1,13 @@
 pub const CHACHA_KEY_SIZE: usize = 32;
-pub const CHACHA_IV_SIZE: usize = 8;
\ No newline at end of file
+pub const CHACHA_IV_SIZE: usize = 8;
+
+// l = 2^252 + 27742317777372353535851937790883648493, little endian
+pub const CURVE_ORDER: [u8; 32] = [
+  0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14,
+  0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+];
+
+pub const IDENTITY_POINT: [u8; 32] = [
+  0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+  0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
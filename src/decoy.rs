@@ -0,0 +1,116 @@
+use rand::distributions::{Distribution, Gamma, Uniform};
+use rand::Rng;
+use rand_core::{CryptoRng, RngCore};
+
+// Fitted to the spend ages of real outputs: ln(age in seconds) ~ Gamma(19.28, 1 / 1.61).
+const GAMMA_SHAPE: f64 = 19.28;
+const GAMMA_SCALE: f64 = 1.0 / 1.61;
+// Outputs younger than this are still locked and cannot be picked.
+const DEFAULT_UNLOCK_BLOCKS: u64 = 10;
+const MAX_PICK_ATTEMPTS: usize = 100;
+
+// Picks decoy outputs by age so that they look like the real spends of a
+// wallet, most of which are recent.
+pub struct DecoySelector {
+  offsets: Vec<u64>,
+  block_time: u64,
+  unlock_blocks: u64,
+}
+
+impl DecoySelector {
+  // offsets[h] is the number of outputs created up to and including block h,
+  // so the outputs of block h are the global indices offsets[h - 1]..offsets[h].
+  pub fn new(offsets: Vec<u64>, block_time: u64) -> DecoySelector {
+    DecoySelector {
+      offsets,
+      block_time,
+      unlock_blocks: DEFAULT_UNLOCK_BLOCKS,
+    }
+  }
+
+  pub fn unlock_blocks(mut self, unlock_blocks: u64) -> DecoySelector {
+    self.unlock_blocks = unlock_blocks;
+    self
+  }
+
+  fn block_outputs(&self, height: usize) -> (u64, u64) {
+    let start = if height == 0 { 0 } else { self.offsets[height - 1] };
+    (start, self.offsets[height])
+  }
+
+  // Samples an age, then a uniformly random output of the block at that age.
+  // None when the age falls before the first block or the block is empty,
+  // a decreasing offset counting as empty.
+  pub fn pick<R: Rng>(&self, rng: &mut R) -> Option<u64> {
+    let spendable = (self.offsets.len() as u64).checked_sub(self.unlock_blocks)?;
+    let age = Gamma::new(GAMMA_SHAPE, GAMMA_SCALE).sample(rng).exp() / self.block_time as f64;
+    if age >= spendable as f64 {
+      return None;
+    }
+    let height = (spendable - 1 - age as u64) as usize;
+    let (start, end) = self.block_outputs(height);
+    if start >= end {
+      return None;
+    }
+    Some(Uniform::new(start, end).sample(rng))
+  }
+
+  // count distinct output indices other than the real one, or None when the
+  // chain does not have enough spendable outputs to pick from.
+  pub fn select(&self, count: usize, real: u64) -> Option<Vec<u64>> {
+    self.select_with_rng(count, real, &mut rand::thread_rng())
+  }
+
+  pub fn select_with_rng<R: RngCore + CryptoRng>(&self, count: usize, real: u64, rng: &mut R) -> Option<Vec<u64>> {
+    let mut selected: Vec<u64> = vec![];
+    let mut attempts = 0;
+    while selected.len() < count {
+      if attempts >= MAX_PICK_ATTEMPTS * count {
+        return None;
+      }
+      attempts += 1;
+      if let Some(index) = self.pick(rng) {
+        if index != real && !selected.contains(&index) {
+          selected.push(index);
+        }
+      }
+    }
+    Some(selected)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn should_select_recent_decoys() {
+    // 100000 blocks of 5 outputs each, two minutes apart
+    let offsets: Vec<u64> = (1..=100_000).map(|h| h * 5).collect();
+    let selector = DecoySelector::new(offsets, 120);
+    let decoys = selector.select(200, 499_900).unwrap();
+    assert!(decoys.len() == 200);
+    let mut sorted = decoys.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert!(sorted.len() == 200);
+    assert!(!decoys.contains(&499_900));
+    // nothing from the locked blocks, and most picks are at most a week old
+    assert!(decoys.iter().all(|index| *index < 5 * (100_000 - 10)));
+    let recent = decoys.iter().filter(|index| **index >= 5 * (100_000 - 5040)).count();
+    assert!(recent > 100);
+  }
+
+  #[test]
+  fn should_fail_without_enough_outputs() {
+    let selector = DecoySelector::new(vec![1, 2, 3], 120);
+    assert!(selector.select(1, 0).is_none());
+    // a young chain has too few old enough outputs even when none are locked
+    let selector = DecoySelector::new(vec![1, 2, 3], 120).unlock_blocks(0);
+    assert!(selector.select(10, 0).is_none());
+    // decreasing offsets are empty blocks rather than a panic
+    let offsets: Vec<u64> = (1..=100_000).map(|h| 500_000 - h * 5).collect();
+    let selector = DecoySelector::new(offsets, 120);
+    assert!(selector.select(1, 0).is_none());
+  }
+}
@@ -1,3 +1,5 @@
+use std::cmp;
+
 extern "C" {
   fn next_difficulty(
     timestamps: *mut u64,
@@ -57,10 +59,237 @@ impl Difficulty {
   }
 }
 
+// A difficulty algorithm reads the timestamps and cumulative difficulties of
+// the blocks before the one to mine, oldest first. Passing the whole chain or
+// only its last history() blocks gives the same difficulty.
+pub trait DifficultyAlgorithm {
+  fn history(&self) -> usize;
+  fn next_difficulty(&self, timestamps: &[u64], cumulative_difficulties: &[u64]) -> u64;
+}
+
+fn tail<T>(values: &[T], size: usize) -> &[T] {
+  &values[values.len().saturating_sub(size)..]
+}
+
+// The original CryptoNote algorithm: the window of blocks lag blocks behind
+// the tip, sorted, with cut outliers dropped at both ends.
+impl DifficultyAlgorithm for Difficulty {
+  fn history(&self) -> usize {
+    self.window as usize + self.lag as usize
+  }
+
+  fn next_difficulty(&self, timestamps: &[u64], cumulative_difficulties: &[u64]) -> u64 {
+    let timestamps = tail(timestamps, self.history());
+    let cumulative_difficulties = tail(cumulative_difficulties, self.history());
+    let n = timestamps.len();
+    let end = if n < self.history() {
+      cmp::min(n, self.window as usize)
+    } else {
+      n - self.lag as usize
+    };
+    let mut sorted = timestamps[..end].to_vec();
+    self.next(&mut sorted, &cumulative_difficulties[..end])
+  }
+}
+
+// Clamps out of order timestamps to one second after the previous one and
+// solve times to 6 targets, yielding (index from 1, solve time). Wraps
+// around like the unsigned arithmetic of the reference.
+fn solve_times(timestamps: &[u64], first_previous: u64, target: u64) -> Vec<(u64, u64)> {
+  let mut previous = first_previous;
+  timestamps[1..]
+    .iter()
+    .enumerate()
+    .map(|(i, timestamp)| {
+      let current = if *timestamp > previous { *timestamp } else { previous.wrapping_add(1) };
+      let solve_time = cmp::min(6 * target, current.wrapping_sub(previous));
+      previous = current;
+      (i as u64 + 1, solve_time)
+    })
+    .collect()
+}
+
+// Zawy's linearly weighted moving average over window solve times, the
+// latest weighing most. Returns guess until window + 1 blocks exist. The
+// target must be positive and the window at least 3.
+pub struct Lwma1 {
+  pub target: u64,
+  pub window: u64,
+  pub guess: u64,
+}
+
+impl DifficultyAlgorithm for Lwma1 {
+  fn history(&self) -> usize {
+    self.window as usize + 1
+  }
+
+  fn next_difficulty(&self, timestamps: &[u64], cumulative_difficulties: &[u64]) -> u64 {
+    assert!(self.target > 0 && self.window >= 3, "LWMA needs a target and a window of 3 blocks or more");
+    if timestamps.len() < self.history() {
+      return self.guess;
+    }
+    let timestamps = tail(timestamps, self.history());
+    let cumulative_difficulties = tail(cumulative_difficulties, self.history());
+    let (t, n) = (self.target as u128, self.window as u128);
+
+    let mut weighted: u128 = solve_times(timestamps, timestamps[0].wrapping_sub(self.target), self.target)
+      .iter()
+      .map(|(i, solve_time)| (*i * solve_time) as u128)
+      .sum();
+    weighted = cmp::max(weighted, n * n * t / 20);
+    let average = (cumulative_difficulties[n as usize] - cumulative_difficulties[0]) as u128 / n;
+    // the two orders of the reference, which avoid round off for small
+    // difficulties and overflow for large ones
+    let mut next = if average > 2_000_000 * n * n * t {
+      (average / (200 * weighted)) * (n * (n + 1) * t * 99)
+    } else {
+      (average * n * (n + 1) * t * 99) / (200 * weighted)
+    };
+    // keeps the 3 most significant digits
+    let mut i: u128 = 1_000_000_000;
+    while i > 1 {
+      if next > i * 100 {
+        next = (next + i / 2) / i * i;
+        break;
+      }
+      i /= 10;
+    }
+    next as u64
+  }
+}
+
+// LWMA-3: LWMA-1 with the change limited to [0.67, 1.5] times the previous
+// difficulty, and at least 8% up after 3 blocks solved in under 0.8 target.
+// Returns guess until window + 1 blocks exist. The target must be positive
+// and the window at least 3, the blocks the 8% rule looks at.
+pub struct Lwma3 {
+  pub target: u64,
+  pub window: u64,
+  pub guess: u64,
+}
+
+impl DifficultyAlgorithm for Lwma3 {
+  fn history(&self) -> usize {
+    self.window as usize + 1
+  }
+
+  fn next_difficulty(&self, timestamps: &[u64], cumulative_difficulties: &[u64]) -> u64 {
+    assert!(self.target > 0 && self.window >= 3, "LWMA needs a target and a window of 3 blocks or more");
+    if timestamps.len() < self.history() {
+      return self.guess;
+    }
+    let timestamps = tail(timestamps, self.history());
+    let cumulative_difficulties = tail(cumulative_difficulties, self.history());
+    let (t, n) = (self.target as u128, self.window as u128);
+
+    let solve_times = solve_times(timestamps, timestamps[0], self.target);
+    let weighted: u128 = solve_times.iter().map(|(i, solve_time)| (*i * solve_time) as u128).sum();
+    let last_three: u64 = solve_times.iter().skip(self.window as usize - 3).map(|(_, s)| s).sum();
+    let last = self.window as usize;
+    let work = (cumulative_difficulties[last] - cumulative_difficulties[0]) as u128;
+    let previous = (cumulative_difficulties[last] - cumulative_difficulties[last - 1]) as u128;
+
+    let mut next = (work * t * (n + 1) * 99) / (100 * 2 * weighted);
+    next = cmp::max(previous * 67 / 100, cmp::min(next, previous * 150 / 100));
+    if last_three < 8 * self.target / 10 {
+      next = cmp::max(next, previous * 108 / 100);
+    }
+    next as u64
+  }
+}
+
+// Zawy v1, a simple moving average: CryptoNote's algorithm without cut or
+// lag, the work of the last window blocks times the target over the span of
+// their sorted timestamps, rounded up. zawy12 recommends a window of 17. The
+// target must be positive.
+pub struct Zawy {
+  pub target: u64,
+  pub window: u64,
+}
+
+impl DifficultyAlgorithm for Zawy {
+  fn history(&self) -> usize {
+    self.window as usize
+  }
+
+  fn next_difficulty(&self, timestamps: &[u64], cumulative_difficulties: &[u64]) -> u64 {
+    assert!(self.target > 0, "Zawy needs a target");
+    let mut sorted = tail(timestamps, self.history()).to_vec();
+    let cumulative_difficulties = tail(cumulative_difficulties, self.history());
+    let n = sorted.len();
+    if n <= 1 {
+      return 1;
+    }
+    sorted.sort_unstable();
+    let span = cmp::max(1, sorted[n - 1] - sorted[0]) as u128;
+    let work = (cumulative_difficulties[n - 1] - cumulative_difficulties[0]) as u128;
+    let next = (work * self.target as u128).div_ceil(span);
+    // 0 on overflow, as the reference
+    if next > u64::MAX as u128 {
+      0
+    } else {
+      next as u64
+    }
+  }
+}
+
+// What the algorithm taking over at a switch reads of the blocks before it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SwitchHistory {
+  // The whole chain, as LWMA forks do, so the first difficulty after the
+  // switch already follows the hash rate.
+  Full,
+  // Only the blocks mined under it, starting from its guess as it would on a
+  // new chain, for forks that hard code the difficulty after the switch.
+  FromSwitch,
+}
+
+// The algorithm of each height, switching at the given heights.
+pub struct DifficultySchedule {
+  algorithms: Vec<(u64, SwitchHistory, Box<dyn DifficultyAlgorithm>)>,
+}
+
+impl DifficultySchedule {
+  pub fn new(algorithm: Box<dyn DifficultyAlgorithm>) -> DifficultySchedule {
+    DifficultySchedule {
+      algorithms: vec![(0, SwitchHistory::Full, algorithm)],
+    }
+  }
+
+  // Heights must increase from one switch to the next.
+  pub fn switch_at(
+    mut self,
+    height: u64,
+    history: SwitchHistory,
+    algorithm: Box<dyn DifficultyAlgorithm>,
+  ) -> DifficultySchedule {
+    assert!(height > self.algorithms.last().unwrap().0, "switch heights must increase");
+    self.algorithms.push((height, history, algorithm));
+    self
+  }
+
+  // The algorithm of height and the first height it reads.
+  pub fn algorithm(&self, height: u64) -> (u64, &dyn DifficultyAlgorithm) {
+    let (start, history, algorithm) = self.algorithms.iter().rev().find(|(start, _, _)| *start <= height).unwrap();
+    match history {
+      SwitchHistory::Full => (0, algorithm.as_ref()),
+      SwitchHistory::FromSwitch => (*start, algorithm.as_ref()),
+    }
+  }
+
+  // Difficulty of the block at height, from the timestamps and cumulative
+  // difficulties of blocks 0 to height - 1.
+  pub fn next_difficulty(&self, height: u64, timestamps: &[u64], cumulative_difficulties: &[u64]) -> u64 {
+    let (begin, algorithm) = self.algorithm(height);
+    let end = cmp::min(height as usize, timestamps.len());
+    let begin = cmp::min(begin as usize, end);
+    algorithm.next_difficulty(&timestamps[begin..end], &cumulative_difficulties[begin..end])
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
-  use std::cmp;
   use std::fs::{canonicalize, File};
   use std::io::{prelude::*, BufReader};
   use std::path::PathBuf;
@@ -122,4 +351,111 @@ mod tests {
       n += 1;
     }
   }
-}
\ No newline at end of file
+
+  // Replays a "timestamp difficulty" file, each difficulty computed from the
+  // blocks before it, and checks the same result from the history() tail.
+  fn check_data_file(file: &str, algorithm: &dyn DifficultyAlgorithm) {
+    let f = File::open(canonicalize(PathBuf::from(file)).unwrap()).unwrap();
+    let mut timestamps: Vec<u64> = vec![];
+    let mut cumulative_difficulties: Vec<u64> = vec![];
+    for line in BufReader::new(&f).lines() {
+      let l = line.unwrap();
+      let split: Vec<&str> = l.split_whitespace().collect();
+      let timestamp = split[0].parse::<u64>().unwrap();
+      let difficulty = split[1].parse::<u64>().unwrap();
+      assert!(algorithm.next_difficulty(&timestamps, &cumulative_difficulties) == difficulty);
+      let begin = timestamps.len().saturating_sub(algorithm.history());
+      assert!(algorithm.next_difficulty(&timestamps[begin..], &cumulative_difficulties[begin..]) == difficulty);
+      timestamps.push(timestamp);
+      cumulative_difficulties.push(cumulative_difficulties.last().unwrap_or(&0) + difficulty);
+    }
+  }
+
+  #[test]
+  fn should_replay_difficulty_algorithms() {
+    let classic = Difficulty {
+      target: 120,
+      window: 720,
+      cut: 60,
+      lag: 15,
+    };
+    check_data_file("./tests/difficulty.txt", &classic);
+    // simulated timestamps, difficulties computed by zawy12's reference C++
+    // and CryptoNote's, see tests/difficulty-reference.cpp
+    let lwma1 = Lwma1 {
+      target: 120,
+      window: 90,
+      guess: 1000,
+    };
+    check_data_file("./tests/difficulty-lwma1.txt", &lwma1);
+    let lwma3 = Lwma3 {
+      target: 120,
+      window: 60,
+      guess: 1000,
+    };
+    check_data_file("./tests/difficulty-lwma3.txt", &lwma3);
+    check_data_file("./tests/difficulty-zawy.txt", &Zawy { target: 120, window: 17 });
+    // Zawy v1 is the original algorithm without cut or lag
+    let sma = Difficulty {
+      target: 120,
+      window: 17,
+      cut: 0,
+      lag: 0,
+    };
+    check_data_file("./tests/difficulty-zawy.txt", &sma);
+  }
+
+  #[test]
+  fn should_handle_early_timestamps() {
+    let lwma1 = Lwma1 {
+      target: 120,
+      window: 3,
+      guess: 1000,
+    };
+    // the reference starts one target before the first timestamp, which
+    // wraps around when the chain starts below the target, so every block
+    // looks solved in a second
+    assert!(lwma1.next_difficulty(&[60, 180, 300, 420], &[1000, 2000, 3000, 4000]) == 13200);
+    assert!(lwma1.next_difficulty(&[119, 239, 359, 479], &[1000, 2000, 3000, 4000]) == 660);
+  }
+
+  #[test]
+  #[should_panic(expected = "window of 3 blocks")]
+  fn should_reject_short_lwma_windows() {
+    let lwma3 = Lwma3 {
+      target: 120,
+      window: 2,
+      guess: 1000,
+    };
+    lwma3.next_difficulty(&[0, 120, 240], &[1000, 2000, 3000]);
+  }
+
+  #[test]
+  fn should_select_algorithm_by_height() {
+    let lwma3 = || {
+      Box::new(Lwma3 {
+        target: 120,
+        window: 60,
+        guess: 5000,
+      })
+    };
+    let zawy = || Box::new(Zawy { target: 120, window: 17 });
+    let fresh = DifficultySchedule::new(zawy()).switch_at(100, SwitchHistory::FromSwitch, lwma3());
+    let forked = DifficultySchedule::new(zawy()).switch_at(100, SwitchHistory::Full, lwma3());
+    assert!(fresh.algorithm(99).0 == 0 && fresh.algorithm(100).0 == 100);
+    assert!(forked.algorithm(99).0 == 0 && forked.algorithm(100).0 == 0);
+    assert!(fresh.algorithm(1_000_000).1.history() == 61);
+
+    let timestamps: Vec<u64> = (0..200).map(|i| 1_500_000_000 + 120 * i).collect();
+    let cumulative_difficulties: Vec<u64> = (0..200).map(|i| 1000 * (i + 1)).collect();
+    assert!(fresh.next_difficulty(99, &timestamps, &cumulative_difficulties) == 1000);
+    assert!(forked.next_difficulty(99, &timestamps, &cumulative_difficulties) == 1000);
+    // LWMA-3 starts from its guess until 61 blocks were mined under it
+    assert!(fresh.next_difficulty(100, &timestamps, &cumulative_difficulties) == 5000);
+    assert!(fresh.next_difficulty(160, &timestamps, &cumulative_difficulties) == 5000);
+    assert!(fresh.next_difficulty(161, &timestamps, &cumulative_difficulties) == 990);
+    // or reads the blocks before the switch right away
+    assert!(forked.next_difficulty(100, &timestamps, &cumulative_difficulties) == 990);
+    assert!(forked.next_difficulty(160, &timestamps, &cumulative_difficulties) == 990);
+  }
+}
@@ -0,0 +1,450 @@
+use super::hash::Hash;
+use super::key::Key;
+use super::serialization::{read_varint, write_varint};
+
+const TAG_PADDING: u8 = 0x00;
+const TAG_PUBLIC_KEY: u8 = 0x01;
+const TAG_NONCE: u8 = 0x02;
+const TAG_MERGE_MINING: u8 = 0x03;
+const TAG_ADDITIONAL_PUBLIC_KEYS: u8 = 0x04;
+// first byte of a nonce holding a payment ID
+const NONCE_PAYMENT_ID: u8 = 0x00;
+const NONCE_ENCRYPTED_PAYMENT_ID: u8 = 0x01;
+const ENCRYPTED_PAYMENT_ID_TAIL: u8 = 0x8d;
+// padding counts its tag, a nonce doesn't count its tag and size
+pub const EXTRA_PADDING_MAX_SIZE: usize = 255;
+pub const EXTRA_NONCE_MAX_SIZE: usize = 255;
+
+#[derive(Debug, PartialEq)]
+pub enum ExtraError {
+  UnexpectedEnd,
+  InvalidVarint,
+  UnknownTag,
+  InvalidPadding,
+  InvalidNonce,
+  InvalidMergeMiningTag,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExtraField {
+  // zero bytes up to the end of extra, counting the tag
+  Padding(usize),
+  PublicKey([u8; 32]),
+  Nonce(Vec<u8>),
+  MergeMiningTag { depth: u64, merkle_root: [u8; 32] },
+  AdditionalPublicKeys(Vec<[u8; 32]>),
+}
+
+fn varint(data: &[u8], offset: &mut usize) -> Result<u64, ExtraError> {
+  read_varint(data, offset).map_err(|_| {
+    if *offset >= data.len() {
+      ExtraError::UnexpectedEnd
+    } else {
+      ExtraError::InvalidVarint
+    }
+  })
+}
+
+fn bytes<'a>(data: &'a [u8], offset: &mut usize, size: u64) -> Result<&'a [u8], ExtraError> {
+  if size > (data.len() - *offset) as u64 {
+    return Err(ExtraError::UnexpectedEnd);
+  }
+  let bytes = &data[*offset..*offset + size as usize];
+  *offset += size as usize;
+  Ok(bytes)
+}
+
+fn key(data: &[u8], offset: &mut usize) -> Result<[u8; 32], ExtraError> {
+  let mut key: [u8; 32] = [0; 32];
+  key.copy_from_slice(bytes(data, offset, 32)?);
+  Ok(key)
+}
+
+impl ExtraField {
+  fn read(data: &[u8], offset: &mut usize) -> Result<ExtraField, ExtraError> {
+    let tag = data[*offset];
+    *offset += 1;
+    match tag {
+      TAG_PADDING => {
+        let size = data.len() - *offset + 1;
+        if size > EXTRA_PADDING_MAX_SIZE || data[*offset..].iter().any(|b| *b != 0) {
+          return Err(ExtraError::InvalidPadding);
+        }
+        *offset = data.len();
+        Ok(ExtraField::Padding(size))
+      }
+      TAG_PUBLIC_KEY => Ok(ExtraField::PublicKey(key(data, offset)?)),
+      TAG_NONCE => {
+        let size = varint(data, offset)?;
+        if size > EXTRA_NONCE_MAX_SIZE as u64 {
+          return Err(ExtraError::InvalidNonce);
+        }
+        Ok(ExtraField::Nonce(bytes(data, offset, size)?.to_vec()))
+      }
+      TAG_MERGE_MINING => {
+        // the depth and root are wrapped in a sized string
+        let size = varint(data, offset)?;
+        let field = bytes(data, offset, size)?;
+        let mut inner = 0;
+        let depth = read_varint(field, &mut inner).map_err(|_| ExtraError::InvalidMergeMiningTag)?;
+        let merkle_root = key(field, &mut inner).map_err(|_| ExtraError::InvalidMergeMiningTag)?;
+        if inner != field.len() {
+          return Err(ExtraError::InvalidMergeMiningTag);
+        }
+        Ok(ExtraField::MergeMiningTag { depth, merkle_root })
+      }
+      TAG_ADDITIONAL_PUBLIC_KEYS => {
+        let count = varint(data, offset)?;
+        if count > ((data.len() - *offset) / 32) as u64 {
+          return Err(ExtraError::UnexpectedEnd);
+        }
+        let mut keys = vec![];
+        for _ in 0..count {
+          keys.push(key(data, offset)?);
+        }
+        Ok(ExtraField::AdditionalPublicKeys(keys))
+      }
+      _ => Err(ExtraError::UnknownTag),
+    }
+  }
+
+  fn write(&self, data: &mut Vec<u8>) -> Result<(), ExtraError> {
+    match self {
+      ExtraField::Padding(size) => {
+        if *size == 0 || *size > EXTRA_PADDING_MAX_SIZE {
+          return Err(ExtraError::InvalidPadding);
+        }
+        data.resize(data.len() + *size, TAG_PADDING);
+      }
+      ExtraField::PublicKey(key) => {
+        data.push(TAG_PUBLIC_KEY);
+        data.extend_from_slice(key);
+      }
+      ExtraField::Nonce(nonce) => {
+        if nonce.len() > EXTRA_NONCE_MAX_SIZE {
+          return Err(ExtraError::InvalidNonce);
+        }
+        data.push(TAG_NONCE);
+        write_varint(data, nonce.len() as u64);
+        data.extend_from_slice(nonce);
+      }
+      ExtraField::MergeMiningTag { depth, merkle_root } => {
+        let mut field = vec![];
+        write_varint(&mut field, *depth);
+        field.extend_from_slice(merkle_root);
+        data.push(TAG_MERGE_MINING);
+        write_varint(data, field.len() as u64);
+        data.extend_from_slice(&field);
+      }
+      ExtraField::AdditionalPublicKeys(keys) => {
+        data.push(TAG_ADDITIONAL_PUBLIC_KEYS);
+        write_varint(data, keys.len() as u64);
+        for key in keys {
+          data.extend_from_slice(key);
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+// The fields of a transaction's tx_extra, in order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Extra {
+  pub fields: Vec<ExtraField>,
+}
+
+impl Extra {
+  // Fails on the first unknown or malformed field.
+  pub fn parse(data: &[u8]) -> Result<Extra, ExtraError> {
+    let mut fields = vec![];
+    let mut offset = 0;
+    while offset < data.len() {
+      fields.push(ExtraField::read(data, &mut offset)?);
+    }
+    Ok(Extra { fields })
+  }
+
+  // The fields before the first unknown or malformed one, as wallets read
+  // extra fields written by software they don't know.
+  pub fn parse_lenient(data: &[u8]) -> Extra {
+    let mut fields = vec![];
+    let mut offset = 0;
+    while offset < data.len() {
+      match ExtraField::read(data, &mut offset) {
+        Ok(field) => fields.push(field),
+        Err(_) => break,
+      }
+    }
+    Extra { fields }
+  }
+
+  pub fn to_bytes(&self) -> Result<Vec<u8>, ExtraError> {
+    let mut data = vec![];
+    for (i, field) in self.fields.iter().enumerate() {
+      if let ExtraField::Padding(_) = field {
+        if i + 1 != self.fields.len() {
+          return Err(ExtraError::InvalidPadding);
+        }
+      }
+      field.write(&mut data)?;
+    }
+    Ok(data)
+  }
+
+  // The transaction public key R, the first one if there are several.
+  pub fn public_key(&self) -> Option<[u8; 32]> {
+    self.fields.iter().find_map(|field| match field {
+      ExtraField::PublicKey(key) => Some(*key),
+      _ => None,
+    })
+  }
+
+  // One key per output, for transactions paying subaddresses.
+  pub fn additional_public_keys(&self) -> Vec<[u8; 32]> {
+    self
+      .fields
+      .iter()
+      .find_map(|field| match field {
+        ExtraField::AdditionalPublicKeys(keys) => Some(keys.clone()),
+        _ => None,
+      })
+      .unwrap_or_default()
+  }
+
+  pub fn nonce(&self) -> Option<&[u8]> {
+    self.fields.iter().find_map(|field| match field {
+      ExtraField::Nonce(nonce) => Some(nonce.as_slice()),
+      _ => None,
+    })
+  }
+
+  // The encrypted payment ID of the first nonce that holds one.
+  pub fn encrypted_payment_id(&self) -> Option<[u8; 8]> {
+    self.fields.iter().find_map(|field| match field {
+      ExtraField::Nonce(nonce) => PaymentId::from_nonce(nonce),
+      _ => None,
+    })
+  }
+
+  pub fn merge_mining_tag(&self) -> Option<(u64, [u8; 32])> {
+    self.fields.iter().find_map(|field| match field {
+      ExtraField::MergeMiningTag { depth, merkle_root } => Some((*depth, *merkle_root)),
+      _ => None,
+    })
+  }
+}
+
+// Builds a tx_extra in the order fields are added, padding always last.
+#[derive(Default)]
+pub struct ExtraBuilder {
+  fields: Vec<ExtraField>,
+  padding: usize,
+}
+
+impl ExtraBuilder {
+  pub fn new() -> ExtraBuilder {
+    ExtraBuilder::default()
+  }
+
+  pub fn public_key(mut self, key: &[u8; 32]) -> ExtraBuilder {
+    self.fields.push(ExtraField::PublicKey(*key));
+    self
+  }
+
+  pub fn nonce(mut self, nonce: &[u8]) -> ExtraBuilder {
+    self.fields.push(ExtraField::Nonce(nonce.to_vec()));
+    self
+  }
+
+  pub fn encrypted_payment_id(self, encrypted: &[u8; 8]) -> ExtraBuilder {
+    self.nonce(&PaymentId::to_nonce(encrypted))
+  }
+
+  pub fn merge_mining_tag(mut self, depth: u64, merkle_root: &[u8; 32]) -> ExtraBuilder {
+    self.fields.push(ExtraField::MergeMiningTag {
+      depth,
+      merkle_root: *merkle_root,
+    });
+    self
+  }
+
+  pub fn additional_public_keys(mut self, keys: &[[u8; 32]]) -> ExtraBuilder {
+    self.fields.push(ExtraField::AdditionalPublicKeys(keys.to_vec()));
+    self
+  }
+
+  pub fn padding(mut self, size: usize) -> ExtraBuilder {
+    self.padding = size;
+    self
+  }
+
+  pub fn build(&self) -> Result<Vec<u8>, ExtraError> {
+    let mut extra = Extra {
+      fields: self.fields.clone(),
+    };
+    if self.padding > 0 {
+      extra.fields.push(ExtraField::Padding(self.padding));
+    }
+    extra.to_bytes()
+  }
+}
+
+// The 8 byte payment IDs of integrated addresses, XORed with the first bytes
+// of H(derivation || 0x8d) so that only the recipient can read them.
+pub struct PaymentId {}
+
+impl PaymentId {
+  // The sender passes the recipient's view public key and the transaction
+  // secret key, the recipient the transaction public key and its view secret
+  // key: both get the same derivation.
+  pub fn encrypt(payment_id: &[u8; 8], public_key: &[u8; 32], secret_key: &[u8; 32]) -> [u8; 8] {
+    let mut data = Key::generate_key_derivation(public_key, secret_key).to_vec();
+    data.push(ENCRYPTED_PAYMENT_ID_TAIL);
+    let hash = Hash::fast(&data);
+    let mut encrypted = *payment_id;
+    for (byte, k) in encrypted.iter_mut().zip(hash.iter()) {
+      *byte ^= k;
+    }
+    encrypted
+  }
+
+  pub fn decrypt(encrypted: &[u8; 8], public_key: &[u8; 32], secret_key: &[u8; 32]) -> [u8; 8] {
+    PaymentId::encrypt(encrypted, public_key, secret_key)
+  }
+
+  // 0x01 || encrypted payment ID, the content of an extra nonce.
+  pub fn to_nonce(encrypted: &[u8; 8]) -> Vec<u8> {
+    let mut nonce = vec![NONCE_ENCRYPTED_PAYMENT_ID];
+    nonce.extend_from_slice(encrypted);
+    nonce
+  }
+
+  pub fn from_nonce(nonce: &[u8]) -> Option<[u8; 8]> {
+    if nonce.len() != 9 || nonce[0] != NONCE_ENCRYPTED_PAYMENT_ID {
+      return None;
+    }
+    let mut encrypted: [u8; 8] = [0; 8];
+    encrypted.copy_from_slice(&nonce[1..]);
+    Some(encrypted)
+  }
+
+  // 0x00 || payment ID, the older unencrypted 32 byte payment IDs.
+  pub fn from_unencrypted_nonce(nonce: &[u8]) -> Option<[u8; 32]> {
+    if nonce.len() != 33 || nonce[0] != NONCE_PAYMENT_ID {
+      return None;
+    }
+    let mut payment_id: [u8; 32] = [0; 32];
+    payment_id.copy_from_slice(&nonce[1..]);
+    Some(payment_id)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn should_parse_extra() {
+    // extra of the genesis transaction in Hash::fast's test
+    let data = hex::decode("01a9a4569f7e10164a32324b2b878ae32d98be0949ce6e0150ba1d7e54d60969e5").unwrap();
+    let extra = Extra::parse(&data).unwrap();
+    assert!(extra.public_key().unwrap() == data[1..]);
+    assert!(extra.nonce().is_none() && extra.additional_public_keys().is_empty());
+    assert!(extra.to_bytes() == Ok(data));
+
+    let data = hex::decode(concat!(
+      "02090100000000000000ff",
+      "03210a0505050505050505050505050505050505050505050505050505050505050505",
+      "0402",
+      "0606060606060606060606060606060606060606060606060606060606060606",
+      "0707070707070707070707070707070707070707070707070707070707070707",
+      "000000"
+    ))
+    .unwrap();
+    let extra = Extra::parse(&data).unwrap();
+    assert!(extra.nonce() == Some(&[1, 0, 0, 0, 0, 0, 0, 0, 0xff][..]));
+    assert!(extra.merge_mining_tag() == Some((10, [5; 32])));
+    assert!(extra.additional_public_keys() == vec![[6; 32], [7; 32]]);
+    assert!(extra.fields[3] == ExtraField::Padding(3));
+    assert!(extra.to_bytes() == Ok(data));
+  }
+
+  #[test]
+  fn should_parse_malformed_extra_leniently() {
+    let mut data = ExtraBuilder::new().public_key(&[1; 32]).build().unwrap();
+    data.extend_from_slice(&[0xde, 1, 2, 3]);
+    assert!(Extra::parse(&data) == Err(ExtraError::UnknownTag));
+    let extra = Extra::parse_lenient(&data);
+    assert!(extra.fields == vec![ExtraField::PublicKey([1; 32])]);
+
+    assert!(Extra::parse(&[1, 0, 0]) == Err(ExtraError::UnexpectedEnd));
+    assert!(Extra::parse(&[2, 5, 1]) == Err(ExtraError::UnexpectedEnd));
+    assert!(Extra::parse(&[2, 0x80, 0x02]) == Err(ExtraError::InvalidNonce));
+    assert!(Extra::parse(&[0, 0, 1]) == Err(ExtraError::InvalidPadding));
+    assert!(Extra::parse(&vec![0; 256]) == Err(ExtraError::InvalidPadding));
+    assert!(Extra::parse(&[3, 1, 0]) == Err(ExtraError::InvalidMergeMiningTag));
+    assert!(Extra::parse(&[4, 0xff, 0xff, 0x03]) == Err(ExtraError::UnexpectedEnd));
+    assert!(Extra::parse_lenient(&[4, 2, 1]).fields.is_empty());
+  }
+
+  #[test]
+  fn should_build_extra() {
+    let data = ExtraBuilder::new()
+      .public_key(&[1; 32])
+      .additional_public_keys(&[[2; 32]])
+      .nonce(&[9; 9])
+      .padding(4)
+      .build()
+      .unwrap();
+    let extra = Extra::parse(&data).unwrap();
+    assert!(extra.public_key() == Some([1; 32]));
+    assert!(extra.additional_public_keys() == vec![[2; 32]]);
+    assert!(extra.nonce() == Some(&[9; 9][..]));
+    assert!(data.ends_with(&[0, 0, 0, 0]) && data.len() == 33 + 34 + 11 + 4);
+
+    assert!(ExtraBuilder::new().nonce(&[0; 256]).build() == Err(ExtraError::InvalidNonce));
+    assert!(ExtraBuilder::new().padding(256).build() == Err(ExtraError::InvalidPadding));
+    let misplaced = Extra {
+      fields: vec![ExtraField::Padding(1), ExtraField::PublicKey([1; 32])],
+    };
+    assert!(misplaced.to_bytes() == Err(ExtraError::InvalidPadding));
+  }
+
+  #[test]
+  fn should_encrypt_payment_ids() {
+    let mut view_public: [u8; 32] = [0; 32];
+    let mut view_secret: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut view_public, &mut view_secret);
+    let mut tx_public: [u8; 32] = [0; 32];
+    let mut tx_secret: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut tx_public, &mut tx_secret);
+    let payment_id = [0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0];
+
+    let encrypted = PaymentId::encrypt(&payment_id, &view_public, &tx_secret);
+    let mut data = Key::generate_key_derivation(&view_public, &tx_secret).to_vec();
+    data.push(0x8d);
+    let key = Hash::fast(&data);
+    for i in 0..8 {
+      assert!(encrypted[i] == payment_id[i] ^ key[i]);
+    }
+    assert!(PaymentId::decrypt(&encrypted, &tx_public, &view_secret) == payment_id);
+    assert!(PaymentId::decrypt(&encrypted, &view_public, &view_secret) != payment_id);
+
+    let extra = ExtraBuilder::new()
+      .public_key(&tx_public)
+      .encrypted_payment_id(&encrypted)
+      .build()
+      .unwrap();
+    let extra = Extra::parse(&extra).unwrap();
+    assert!(extra.nonce().unwrap()[0] == 1);
+    let received = extra.encrypted_payment_id().unwrap();
+    assert!(PaymentId::decrypt(&received, &extra.public_key().unwrap(), &view_secret) == payment_id);
+
+    assert!(PaymentId::from_nonce(&[0; 9]).is_none());
+    let mut nonce = vec![0];
+    nonce.extend_from_slice(&[7; 32]);
+    assert!(PaymentId::from_unencrypted_nonce(&nonce) == Some([7; 32]));
+    assert!(PaymentId::from_nonce(&nonce).is_none());
+  }
+}
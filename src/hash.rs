@@ -2,6 +2,7 @@ extern "C" {
   fn cn_slow_hash(data: *const u8, length: usize, hash: *mut u8, variant: usize, prehashed: usize);
   fn cn_fast_hash(data: *const u8, length: usize, hash: *mut u8);
   fn check_hash(hash: *const [u8; 32], difficulty: u64) -> bool;
+  fn tree_hash(hashes: *const [u8; 32], count: usize, root_hash: *mut u8);
 }
 
 pub struct Hash {}
@@ -24,6 +25,17 @@ impl Hash {
     hash
   }
 
+  // Merkle root of the transaction hashes of a block, None for no hashes
+  // which the C code asserts against.
+  pub fn tree(hashes: &[[u8; 32]]) -> Option<[u8; 32]> {
+    if hashes.is_empty() {
+      return None;
+    }
+    let mut root: [u8; 32] = [0; 32];
+    unsafe { tree_hash(hashes.as_ptr(), hashes.len(), root.as_mut_ptr()) }
+    Some(root)
+  }
+
   pub fn check_with_difficulty(hash: &[u8; 32], difficulty: u64) -> bool {
     unsafe {
       return check_hash(hash, difficulty);
@@ -49,6 +61,21 @@ mod tests {
     ));
   }
 
+  #[test]
+  fn should_compute_tree_hash() {
+    let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::fast(&[i])).collect();
+    let pair = |a: &[u8; 32], b: &[u8; 32]| Hash::fast(&[&a[..], &b[..]].concat());
+    assert!(Hash::tree(&[]).is_none());
+    assert!(Hash::tree(&hashes[..1]) == Some(hashes[0]));
+    assert!(Hash::tree(&hashes[..2]) == Some(pair(&hashes[0], &hashes[1])));
+    assert!(Hash::tree(&hashes[..3]) == Some(pair(&hashes[0], &pair(&hashes[1], &hashes[2]))));
+    let root = pair(
+      &pair(&hashes[0], &hashes[1]),
+      &pair(&hashes[2], &pair(&hashes[3], &hashes[4])),
+    );
+    assert!(Hash::tree(&hashes) == Some(root));
+  }
+
   #[test]
 
   fn should_test_fast() {
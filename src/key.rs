@@ -1,7 +1,8 @@
-use super::scalar::EllipticCurveScalar;
+use super::hash::Hash;
+use super::scalar::{EllipticCurvePoint, EllipticCurveScalar, Point, Scalar, ThreadRandom};
+use rand_core::{CryptoRng, RngCore};
 
 extern "C" {
-  fn generate_keys(public: *mut u8, secret: *mut u8);
   fn check_public_key(public_key: *const u8) -> bool;
   fn secret_key_to_public_key(secret_key: *const u8, public_key: *mut u8) -> bool;
   fn generate_key_derivation(
@@ -28,23 +29,30 @@ extern "C" {
     derived_key: *mut u8,
   ) -> bool;
 
-  fn generate_signature(
-    prefix_hash: *const u8,
-    public_key: *const u8,
-    secret_key: *const u8,
-    signature: *mut u8,
-  );
-
   fn check_signature(prefix_hash: *const u8, public_key: *const u8, signature: *const u8) -> bool;
 
   fn generate_key_image(public_key: *const u8, secret_key: *const u8, image: *mut u8);
+  fn generate_incomplete_key_image(public_key: *const u8, incomplete_key_image: *mut u8);
 }
 
+const DETERMINISTIC_NONCE_TAG: &[u8] = b"deterministic_nonce";
+
 pub struct Key {}
 
+pub struct KeyImage {}
+
 impl Key {
   pub fn generate_key_pair(public_key: &mut [u8; 32], secret_key: &mut [u8; 32]) {
-    unsafe { generate_keys(public_key.as_mut_ptr(), secret_key.as_mut_ptr()) }
+    Key::generate_key_pair_with_rng(public_key, secret_key, &mut ThreadRandom)
+  }
+
+  pub fn generate_key_pair_with_rng<R: RngCore + CryptoRng>(
+    public_key: &mut [u8; 32],
+    secret_key: &mut [u8; 32],
+    rng: &mut R,
+  ) {
+    EllipticCurveScalar::random_with_rng(secret_key, rng);
+    Key::secret_to_public(secret_key, public_key);
   }
 
   pub fn generate_secret_key() -> [u8; 32] {
@@ -53,6 +61,12 @@ impl Key {
     secret_key
   }
 
+  pub fn generate_secret_key_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> [u8; 32] {
+    let mut secret_key: [u8; 32] = [0; 32];
+    EllipticCurveScalar::random_with_rng(&mut secret_key, rng);
+    secret_key
+  }
+
   pub fn check_public_key(public_key: &[u8; 32]) -> bool {
     unsafe { return check_public_key(public_key.as_ptr()) }
   }
@@ -125,18 +139,63 @@ impl Key {
     public_key: &[u8; 32],
     secret_key: &[u8; 32],
   ) -> [u8; 64] {
+    Key::generate_signature_with_rng(prefix_hash, public_key, secret_key, &mut ThreadRandom)
+  }
+
+  // Nonce derived from the secret key and everything being signed instead of
+  // the global RNG, in the spirit of RFC 6979: the same inputs always give
+  // the same nonce and distinct inputs give unrelated ones. The counter
+  // separates the several nonces a ring signature needs.
+  pub(crate) fn deterministic_nonce(secret_key: &[u8; 32], data: &[u8], counter: u64) -> Scalar {
+    let mut buf: Vec<u8> = DETERMINISTIC_NONCE_TAG.to_vec();
+    buf.extend_from_slice(secret_key);
+    buf.extend_from_slice(&counter.to_le_bytes());
+    buf.extend_from_slice(data);
+    Scalar::hash(&buf)
+  }
+
+  // generate_signature with the nonce k given: c = H_s(prefix || P || k * G),
+  // r = k - c * x.
+  fn generate_signature_with_nonce(
+    prefix_hash: &[u8; 32],
+    public_key: &[u8; 32],
+    secret_key: &[u8; 32],
+    k: &Scalar,
+  ) -> [u8; 64] {
+    let mut data = prefix_hash.to_vec();
+    data.extend_from_slice(public_key);
+    data.extend_from_slice(&Point::mul_base(k).to_bytes());
+    let c = Scalar::hash(&data);
+    let r = c.mul_sub(&Scalar::from(*secret_key), k);
     let mut signature: [u8; 64] = [0; 64];
-    unsafe {
-      generate_signature(
-        prefix_hash.as_ptr(),
-        public_key.as_ptr(),
-        secret_key.as_ptr(),
-        signature.as_mut_ptr(),
-      );
-    }
+    signature[..32].copy_from_slice(c.as_bytes());
+    signature[32..].copy_from_slice(r.as_bytes());
     signature
   }
 
+  pub fn generate_signature_with_rng<R: RngCore + CryptoRng>(
+    prefix_hash: &[u8; 32],
+    public_key: &[u8; 32],
+    secret_key: &[u8; 32],
+    rng: &mut R,
+  ) -> [u8; 64] {
+    let k = Scalar::random_with_rng(rng);
+    Key::generate_signature_with_nonce(prefix_hash, public_key, secret_key, &k)
+  }
+
+  // Same signature as generate_signature, with the nonce from
+  // deterministic_nonce, so it checks with check_signature.
+  pub fn generate_deterministic_signature(
+    prefix_hash: &[u8; 32],
+    public_key: &[u8; 32],
+    secret_key: &[u8; 32],
+  ) -> [u8; 64] {
+    let mut data = prefix_hash.to_vec();
+    data.extend_from_slice(public_key);
+    let k = Key::deterministic_nonce(secret_key, &data, 0);
+    Key::generate_signature_with_nonce(prefix_hash, public_key, secret_key, &k)
+  }
+
   pub fn check_signature(
     prefix_hash: &[u8; 32],
     public_key: &[u8; 32],
@@ -151,6 +210,36 @@ impl Key {
     }
   }
 
+  // H(len(domain) || domain || message) with the length as 8 little endian
+  // bytes, so that no two (domain, message) pairs hash the same bytes and a
+  // signature made for one purpose is never valid for another.
+  pub fn message_hash(domain: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut data: Vec<u8> = (domain.len() as u64).to_le_bytes().to_vec();
+    data.extend_from_slice(domain);
+    data.extend_from_slice(message);
+    Hash::fast(&data)
+  }
+
+  // Signs arbitrary bytes, the result checks with check_signature against
+  // message_hash(domain, message).
+  pub fn sign_message(domain: &[u8], message: &[u8], public_key: &[u8; 32], secret_key: &[u8; 32]) -> [u8; 64] {
+    Key::sign_message_with_rng(domain, message, public_key, secret_key, &mut rand::thread_rng())
+  }
+
+  pub fn sign_message_with_rng<R: RngCore + CryptoRng>(
+    domain: &[u8],
+    message: &[u8],
+    public_key: &[u8; 32],
+    secret_key: &[u8; 32],
+    rng: &mut R,
+  ) -> [u8; 64] {
+    Key::generate_signature_with_rng(&Key::message_hash(domain, message), public_key, secret_key, rng)
+  }
+
+  pub fn check_message_signature(domain: &[u8], message: &[u8], public_key: &[u8; 32], signature: &[u8; 64]) -> bool {
+    Key::check_signature(&Key::message_hash(domain, message), public_key, signature)
+  }
+
   pub fn generate_key_image(public_key: &[u8; 32], secret_key: &[u8; 32]) -> [u8; 32] {
     let mut image: [u8; 32] = [0; 32];
     unsafe {
@@ -158,11 +247,48 @@ impl Key {
     }
     image
   }
+
+  // Hp(P), the key image base point which needs no secret key.
+  pub fn generate_incomplete_key_image(public_key: &[u8; 32]) -> [u8; 32] {
+    let mut image: [u8; 32] = [0; 32];
+    unsafe {
+      generate_incomplete_key_image(public_key.as_ptr(), image.as_mut_ptr());
+    }
+    image
+  }
+
+  // x_i * Hp(P) for a share x_i of the secret key of P.
+  pub fn generate_partial_key_image(public_key: &[u8; 32], secret_share: &[u8; 32]) -> [u8; 32] {
+    let base = Key::generate_incomplete_key_image(public_key);
+    EllipticCurvePoint::scalar_mult(secret_share, &base).unwrap()
+  }
+
+  pub fn combine_key_images(partial_images: &[[u8; 32]]) -> Option<[u8; 32]> {
+    let (first, rest) = partial_images.split_first()?;
+    let mut image = *first;
+    for partial in rest {
+      image = EllipticCurvePoint::add(&image, partial)?;
+    }
+    Some(image)
+  }
+}
+
+impl KeyImage {
+  // A key image is only valid in the prime order subgroup, l * I == 0,
+  // otherwise torsion components allow one output to be spent twice.
+  pub fn is_valid(image: &[u8; 32]) -> bool {
+    match Point::from_bytes(image) {
+      Some(point) => point.is_torsion_free(),
+      None => false,
+    }
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
 
   #[test]
   fn should_get_public_key_from_secret_key() {
@@ -184,6 +310,127 @@ mod tests {
     assert!(Key::check_public_key(&public_key));
   }
 
+  #[test]
+  fn should_combine_partial_key_images() {
+    let mut public_key: [u8; 32] = [0; 32];
+    let mut secret_key: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut public_key, &mut secret_key);
+
+    let share1 = Key::generate_secret_key();
+    let share2 = Key::generate_secret_key();
+    let share3 = EllipticCurveScalar::sub(&EllipticCurveScalar::sub(&secret_key, &share1), &share2);
+    let partials = [
+      Key::generate_partial_key_image(&public_key, &share1),
+      Key::generate_partial_key_image(&public_key, &share2),
+      Key::generate_partial_key_image(&public_key, &share3),
+    ];
+    let image = Key::generate_key_image(&public_key, &secret_key);
+    assert!(Key::combine_key_images(&partials).unwrap() == image);
+    assert!(Key::combine_key_images(&partials[..2]).unwrap() != image);
+    assert!(Key::combine_key_images(&[]).is_none());
+
+    let base = Key::generate_incomplete_key_image(&public_key);
+    assert!(base == EllipticCurveScalar::from_hash(&public_key));
+    assert!(EllipticCurvePoint::scalar_mult(&secret_key, &base).unwrap() == image);
+  }
+
+  #[test]
+  fn should_use_injected_rng() {
+    let mut public_key: [u8; 32] = [0; 32];
+    let mut secret_key: [u8; 32] = [0; 32];
+    Key::generate_key_pair_with_rng(&mut public_key, &mut secret_key, &mut StdRng::seed_from_u64(1));
+    let mut expected: [u8; 32] = [0; 32];
+    Key::secret_to_public(&secret_key, &mut expected);
+    assert!(public_key == expected && EllipticCurveScalar::check(&secret_key));
+    assert!(Key::generate_secret_key_with_rng(&mut StdRng::seed_from_u64(1)) == secret_key);
+    assert!(Key::generate_secret_key_with_rng(&mut StdRng::seed_from_u64(2)) != secret_key);
+
+    let prefix_hash = [3; 32];
+    let signature = Key::generate_signature_with_rng(&prefix_hash, &public_key, &secret_key, &mut StdRng::seed_from_u64(3));
+    assert!(Key::check_signature(&prefix_hash, &public_key, &signature));
+    let again = Key::generate_signature_with_rng(&prefix_hash, &public_key, &secret_key, &mut StdRng::seed_from_u64(3));
+    assert!(signature == again);
+    let fresh = Key::generate_signature_with_rng(&prefix_hash, &public_key, &secret_key, &mut rand::rngs::OsRng::new().unwrap());
+    assert!(Key::check_signature(&prefix_hash, &public_key, &fresh) && fresh != signature);
+
+    let signature = Key::sign_message_with_rng(b"login", b"hi", &public_key, &secret_key, &mut StdRng::seed_from_u64(4));
+    assert!(Key::check_message_signature(b"login", b"hi", &public_key, &signature));
+    assert!(signature == Key::sign_message_with_rng(b"login", b"hi", &public_key, &secret_key, &mut StdRng::seed_from_u64(4)));
+  }
+
+  #[test]
+  fn should_generate_deterministic_signature() {
+    let mut public_key: [u8; 32] = [0; 32];
+    let mut secret_key: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut public_key, &mut secret_key);
+    let prefix_hash = Hash::fast(b"deterministic");
+
+    let signature = Key::generate_deterministic_signature(&prefix_hash, &public_key, &secret_key);
+    assert!(Key::check_signature(&prefix_hash, &public_key, &signature));
+    assert!(signature == Key::generate_deterministic_signature(&prefix_hash, &public_key, &secret_key));
+    let other = Key::generate_deterministic_signature(&[0; 32], &public_key, &secret_key);
+    assert!(other[..32] != signature[..32] && other[32..] != signature[32..]);
+    assert!(Key::generate_signature(&prefix_hash, &public_key, &secret_key) != signature);
+
+    // pinned so that a change in the nonce derivation does not go unnoticed
+    let secret = Scalar::hash(b"deterministic secret").to_bytes();
+    Key::secret_to_public(&secret, &mut public_key);
+    let signature = Key::generate_deterministic_signature(&[1; 32], &public_key, &secret);
+    assert!(Key::check_signature(&[1; 32], &public_key, &signature));
+    let expected = concat!(
+      "455591e4b9d57a27e1cae07bcbc82770a32ab5fd860c487f0c36707d2769ce0b",
+      "fee397cd13b02ad44124928989a17fbd0e616cb1213cf02796e3f3ffc0eaba0c"
+    );
+    assert!(hex::encode(&signature[..]) == expected);
+  }
+
+  #[test]
+  fn should_sign_messages_with_domain() {
+    let mut public_key: [u8; 32] = [0; 32];
+    let mut secret_key: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut public_key, &mut secret_key);
+    let message = b"login challenge 1234";
+
+    let signature = Key::sign_message(b"login", message, &public_key, &secret_key);
+    assert!(Key::check_message_signature(b"login", message, &public_key, &signature));
+    assert!(Key::check_signature(&Key::message_hash(b"login", message), &public_key, &signature));
+    assert!(!Key::check_message_signature(b"proof", message, &public_key, &signature));
+    assert!(!Key::check_message_signature(b"login", b"login challenge 1235", &public_key, &signature));
+    assert!(!Key::check_signature(&Hash::fast(message), &public_key, &signature));
+
+    // the boundary between domain and message is part of the hash
+    assert!(Key::message_hash(b"ab", b"c") != Key::message_hash(b"a", b"bc"));
+    let long = vec![7; 10_000];
+    let signature = Key::sign_message(b"", &long, &public_key, &secret_key);
+    assert!(Key::check_message_signature(b"", &long, &public_key, &signature));
+  }
+
+  #[test]
+  fn should_check_key_image_subgroup() {
+    use super::super::ring::Ring;
+
+    let mut public_key: [u8; 32] = [0; 32];
+    let mut secret_key: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut public_key, &mut secret_key);
+    let image = Key::generate_key_image(&public_key, &secret_key);
+    assert!(KeyImage::is_valid(&image));
+
+    // a point of order 8
+    let torsion = hex::decode("26e8958fc2b227b045c3f489f2ef98f0d5dfac05d3c63339b13802886d53fc05").unwrap();
+    let mut torsion_point: [u8; 32] = [0; 32];
+    torsion_point.copy_from_slice(&torsion);
+    assert!(!KeyImage::is_valid(&torsion_point));
+    let bad_image = EllipticCurvePoint::add(&image, &torsion_point).unwrap();
+    assert!(!KeyImage::is_valid(&bad_image));
+    assert!(!KeyImage::is_valid(&[0xff; 32]));
+
+    let prefix_hash = [7; 32];
+    let pubs = vec![public_key];
+    let signature = Ring::generate_signature(&prefix_hash, &image, &pubs, &secret_key, 0).unwrap();
+    assert!(Ring::check_signature(&prefix_hash, &image, &pubs, &signature));
+    assert!(!Ring::check_signature(&prefix_hash, &bad_image, &pubs, &signature));
+  }
+
   #[test]
   fn should_get_public_key_from_generated_secret_key() {
     let secret_key = Key::generate_secret_key();
@@ -6,4 +6,15 @@ pub mod ring;
 pub mod scalar;
 pub mod difficulty;
 pub mod amount;
+pub mod multisig;
+pub mod multiexp;
+pub mod commitment;
+pub mod bulletproof;
+pub mod mlsag;
+pub mod decoy;
+pub mod base58;
+pub mod proof;
+pub mod serialization;
+pub mod extra;
+pub mod spent;
 
@@ -0,0 +1,328 @@
+use super::key::{Key, KeyImage};
+use super::scalar::{Point, Scalar};
+use rand_core::{CryptoRng, RngCore};
+
+#[derive(Debug, PartialEq)]
+pub enum MlsagError {
+  EmptyRing,
+  InconsistentRing,
+  InvalidIndex,
+  InvalidKeyImageRows,
+  InvalidPublicKey,
+  SecretKeyMismatch,
+}
+
+// ss[i][j] answers the challenge for column j of ring member i, cc is the
+// challenge of the first member and key images cover the first ds_rows
+// columns only, the commitment columns of RingCT being unlinkable.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MlsagSignature {
+  pub ss: Vec<Vec<[u8; 32]>>,
+  pub cc: [u8; 32],
+  pub key_images: Vec<[u8; 32]>,
+}
+
+pub struct Mlsag {}
+
+struct Ring {
+  keys: Vec<Vec<Point>>,
+  hashed: Vec<Vec<Point>>,
+}
+
+fn unpack_ring(pubs: &[Vec<[u8; 32]>], ds_rows: usize) -> Result<Ring, MlsagError> {
+  if pubs.is_empty() || pubs[0].is_empty() {
+    return Err(MlsagError::EmptyRing);
+  }
+  let columns = pubs[0].len();
+  if ds_rows > columns {
+    return Err(MlsagError::InvalidKeyImageRows);
+  }
+  let mut keys = Vec::with_capacity(pubs.len());
+  let mut hashed = Vec::with_capacity(pubs.len());
+  for member in pubs {
+    if member.len() != columns {
+      return Err(MlsagError::InconsistentRing);
+    }
+    let row: Option<Vec<Point>> = member.iter().map(Point::from_bytes).collect();
+    keys.push(row.ok_or(MlsagError::InvalidPublicKey)?);
+    hashed.push(
+      member[..ds_rows]
+        .iter()
+        .map(|key| Point::from_bytes(&Key::generate_incomplete_key_image(key)).unwrap())
+        .collect(),
+    );
+  }
+  Ok(Ring { keys, hashed })
+}
+
+// H_s(message || P_0 || L_0 || R_0 || ... || P_j || L_j) over the columns of
+// one ring member, with R only for the columns that have key images.
+fn challenge(message: &[u8; 32], keys: &[Point], l: &[Point], r: &[Point]) -> Scalar {
+  let mut data = message.to_vec();
+  for (j, key) in keys.iter().enumerate() {
+    data.extend_from_slice(&key.to_bytes());
+    data.extend_from_slice(&l[j].to_bytes());
+    if j < r.len() {
+      data.extend_from_slice(&r[j].to_bytes());
+    }
+  }
+  Scalar::hash(&data)
+}
+
+impl Mlsag {
+  pub fn generate(
+    message: &[u8; 32],
+    pubs: &[Vec<[u8; 32]>],
+    secrets: &[[u8; 32]],
+    index: usize,
+    ds_rows: usize,
+  ) -> Result<MlsagSignature, MlsagError> {
+    Mlsag::generate_with_rng(message, pubs, secrets, index, ds_rows, &mut rand::thread_rng())
+  }
+
+  pub fn generate_with_rng<R: RngCore + CryptoRng>(
+    message: &[u8; 32],
+    pubs: &[Vec<[u8; 32]>],
+    secrets: &[[u8; 32]],
+    index: usize,
+    ds_rows: usize,
+    rng: &mut R,
+  ) -> Result<MlsagSignature, MlsagError> {
+    let ring = unpack_ring(pubs, ds_rows)?;
+    let n = pubs.len();
+    let columns = pubs[0].len();
+    if index >= n {
+      return Err(MlsagError::InvalidIndex);
+    }
+    if secrets.len() != columns {
+      return Err(MlsagError::InconsistentRing);
+    }
+    let secrets: Vec<Scalar> = secrets.iter().map(|s| Scalar::from(*s)).collect();
+    for (secret, key) in secrets.iter().zip(ring.keys[index].iter()) {
+      if Point::mul_base(secret) != *key {
+        return Err(MlsagError::SecretKeyMismatch);
+      }
+    }
+
+    let key_images: Vec<[u8; 32]> = (0..ds_rows)
+      .map(|j| Key::generate_key_image(&pubs[index][j], &secrets[j].to_bytes()))
+      .collect();
+    let images: Vec<Point> = key_images.iter().map(|i| Point::from_bytes(i).unwrap()).collect();
+
+    let alpha: Vec<Scalar> = (0..columns).map(|_| Scalar::random_with_rng(rng)).collect();
+    let l: Vec<Point> = alpha.iter().map(Point::mul_base).collect();
+    let r: Vec<Point> = (0..ds_rows).map(|j| alpha[j] * ring.hashed[index][j]).collect();
+    let mut c = challenge(message, &ring.keys[index], &l, &r);
+
+    let mut ss: Vec<Vec<Scalar>> = vec![vec![]; n];
+    let mut cc = c;
+    let mut i = (index + 1) % n;
+    if i == 0 {
+      cc = c;
+    }
+    while i != index {
+      ss[i] = (0..columns).map(|_| Scalar::random_with_rng(rng)).collect();
+      let l: Vec<Point> = (0..columns)
+        .map(|j| Point::double_mul_base(&c, &ring.keys[i][j], &ss[i][j]))
+        .collect();
+      let r: Vec<Point> = (0..ds_rows)
+        .map(|j| ss[i][j] * ring.hashed[i][j] + c * images[j])
+        .collect();
+      c = challenge(message, &ring.keys[i], &l, &r);
+      i = (i + 1) % n;
+      if i == 0 {
+        cc = c;
+      }
+    }
+    ss[index] = (0..columns).map(|j| c.mul_sub(&secrets[j], &alpha[j])).collect();
+
+    Ok(MlsagSignature {
+      ss: ss.iter().map(|row| row.iter().map(|s| s.to_bytes()).collect()).collect(),
+      cc: cc.to_bytes(),
+      key_images,
+    })
+  }
+
+  pub fn verify(message: &[u8; 32], pubs: &[Vec<[u8; 32]>], signature: &MlsagSignature, ds_rows: usize) -> bool {
+    let ring = match unpack_ring(pubs, ds_rows) {
+      Ok(ring) => ring,
+      Err(_) => return false,
+    };
+    let columns = pubs[0].len();
+    if signature.ss.len() != pubs.len() || signature.key_images.len() != ds_rows {
+      return false;
+    }
+    if signature.key_images.iter().any(|image| !KeyImage::is_valid(image)) {
+      return false;
+    }
+    let images: Vec<Point> = signature
+      .key_images
+      .iter()
+      .map(|i| Point::from_bytes(i).unwrap())
+      .collect();
+    let cc = match Scalar::from_canonical_bytes(&signature.cc) {
+      Some(cc) => cc,
+      None => return false,
+    };
+    let mut c = cc;
+    for (i, row) in signature.ss.iter().enumerate() {
+      if row.len() != columns {
+        return false;
+      }
+      let ss: Option<Vec<Scalar>> = row.iter().map(Scalar::from_canonical_bytes).collect();
+      let ss = match ss {
+        Some(ss) => ss,
+        None => return false,
+      };
+      let l: Vec<Point> = (0..columns)
+        .map(|j| Point::double_mul_base(&c, &ring.keys[i][j], &ss[j]))
+        .collect();
+      let r: Vec<Point> = (0..ds_rows)
+        .map(|j| ss[j] * ring.hashed[i][j] + c * images[j])
+        .collect();
+      c = challenge(message, &ring.keys[i], &l, &r);
+    }
+    c == cc
+  }
+
+  // RingCT simple layout: each ring member is [P_i, C_i - C_pseudo], where
+  // the signer knows x with P = x * G and z with C - C_pseudo = z * G.
+  pub fn generate_simple(
+    message: &[u8; 32],
+    ring: &[([u8; 32], [u8; 32])],
+    pseudo_output: &[u8; 32],
+    secret_key: &[u8; 32],
+    mask_difference: &[u8; 32],
+    index: usize,
+  ) -> Result<MlsagSignature, MlsagError> {
+    Mlsag::generate_simple_with_rng(
+      message,
+      ring,
+      pseudo_output,
+      secret_key,
+      mask_difference,
+      index,
+      &mut rand::thread_rng(),
+    )
+  }
+
+  pub fn generate_simple_with_rng<R: RngCore + CryptoRng>(
+    message: &[u8; 32],
+    ring: &[([u8; 32], [u8; 32])],
+    pseudo_output: &[u8; 32],
+    secret_key: &[u8; 32],
+    mask_difference: &[u8; 32],
+    index: usize,
+    rng: &mut R,
+  ) -> Result<MlsagSignature, MlsagError> {
+    let pubs = Mlsag::simple_matrix(ring, pseudo_output)?;
+    Mlsag::generate_with_rng(message, &pubs, &[*secret_key, *mask_difference], index, 1, rng)
+  }
+
+  pub fn verify_simple(
+    message: &[u8; 32],
+    ring: &[([u8; 32], [u8; 32])],
+    pseudo_output: &[u8; 32],
+    signature: &MlsagSignature,
+  ) -> bool {
+    match Mlsag::simple_matrix(ring, pseudo_output) {
+      Ok(pubs) => Mlsag::verify(message, &pubs, signature, 1),
+      Err(_) => false,
+    }
+  }
+
+  fn simple_matrix(ring: &[([u8; 32], [u8; 32])], pseudo_output: &[u8; 32]) -> Result<Vec<Vec<[u8; 32]>>, MlsagError> {
+    let pseudo = Point::from_bytes(pseudo_output).ok_or(MlsagError::InvalidPublicKey)?;
+    ring
+      .iter()
+      .map(|(key, commitment)| {
+        let commitment = Point::from_bytes(commitment).ok_or(MlsagError::InvalidPublicKey)?;
+        Ok(vec![*key, (commitment - pseudo).to_bytes()])
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::commitment::Commitment;
+  use super::*;
+
+  fn key_pair() -> ([u8; 32], [u8; 32]) {
+    let mut public_key: [u8; 32] = [0; 32];
+    let mut secret_key: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut public_key, &mut secret_key);
+    (public_key, secret_key)
+  }
+
+  #[test]
+  fn should_sign_key_matrix() {
+    let message = [3; 32];
+    for (n, columns, ds_rows) in &[(1, 1, 1), (4, 2, 1), (5, 3, 2), (3, 2, 0)] {
+      for index in 0..*n {
+        let mut secrets: Vec<[u8; 32]> = vec![];
+        let mut pubs: Vec<Vec<[u8; 32]>> = vec![];
+        for i in 0..*n {
+          let mut row = vec![];
+          for _ in 0..*columns {
+            let (public_key, secret_key) = key_pair();
+            row.push(public_key);
+            if i == index {
+              secrets.push(secret_key);
+            }
+          }
+          pubs.push(row);
+        }
+        let signature = Mlsag::generate(&message, &pubs, &secrets, index, *ds_rows).unwrap();
+        assert!(signature.key_images.len() == *ds_rows);
+        if *ds_rows > 0 {
+          assert!(signature.key_images[0] == Key::generate_key_image(&pubs[index][0], &secrets[0]));
+        }
+        assert!(Mlsag::verify(&message, &pubs, &signature, *ds_rows));
+        assert!(!Mlsag::verify(&[4; 32], &pubs, &signature, *ds_rows));
+
+        let mut tampered = signature.clone();
+        tampered.ss[0][0] = Scalar::from(tampered.ss[0][0]).mul_add(&Scalar::one(), &Scalar::one()).to_bytes();
+        assert!(!Mlsag::verify(&message, &pubs, &tampered, *ds_rows));
+      }
+    }
+  }
+
+  #[test]
+  fn should_reject_invalid_inputs() {
+    let (public_key, secret_key) = key_pair();
+    let (other, _) = key_pair();
+    let pubs = vec![vec![public_key], vec![other]];
+    assert!(Mlsag::generate(&[0; 32], &pubs, &[secret_key], 1, 1).err() == Some(MlsagError::SecretKeyMismatch));
+    assert!(Mlsag::generate(&[0; 32], &pubs, &[secret_key], 2, 1).err() == Some(MlsagError::InvalidIndex));
+    assert!(Mlsag::generate(&[0; 32], &pubs, &[secret_key], 0, 2).err() == Some(MlsagError::InvalidKeyImageRows));
+    assert!(Mlsag::generate(&[0; 32], &[], &[secret_key], 0, 1).err() == Some(MlsagError::EmptyRing));
+    let uneven = vec![vec![public_key], vec![other, other]];
+    assert!(Mlsag::generate(&[0; 32], &uneven, &[secret_key], 0, 1).err() == Some(MlsagError::InconsistentRing));
+  }
+
+  #[test]
+  fn should_sign_ringct_inputs() {
+    let message = [9; 32];
+    let amount = 1000;
+    let (public_key, secret_key) = key_pair();
+    let input_mask = Scalar::random();
+    let pseudo_mask = Scalar::random();
+    let pseudo_output = Commitment::commit(&pseudo_mask, amount).to_bytes();
+
+    let mut ring: Vec<([u8; 32], [u8; 32])> = (0..4)
+      .map(|i| (key_pair().0, Commitment::commit(&Scalar::random(), i).to_bytes()))
+      .collect();
+    ring.insert(1, (public_key, Commitment::commit(&input_mask, amount).to_bytes()));
+
+    let mask_difference = (input_mask - pseudo_mask).to_bytes();
+    let signature = Mlsag::generate_simple(&message, &ring, &pseudo_output, &secret_key, &mask_difference, 1).unwrap();
+    assert!(signature.key_images == vec![Key::generate_key_image(&public_key, &secret_key)]);
+    assert!(Mlsag::verify_simple(&message, &ring, &pseudo_output, &signature));
+
+    // a pseudo output committing to another amount cannot be signed for
+    let wrong_output = Commitment::commit(&pseudo_mask, amount + 1).to_bytes();
+    assert!(Mlsag::generate_simple(&message, &ring, &wrong_output, &secret_key, &mask_difference, 1).err() == Some(MlsagError::SecretKeyMismatch));
+    assert!(!Mlsag::verify_simple(&message, &ring, &wrong_output, &signature));
+  }
+}
@@ -0,0 +1,175 @@
+use super::scalar::{GeCached, Point, Scalar};
+
+// Below this many terms Straus' interleaved windows beat Pippenger's buckets.
+const STRAUS_THRESHOLD: usize = 64;
+
+pub struct MultiExp {}
+
+// Signed radix 2^w digits of a scalar, each in [-2^(w-1), 2^(w-1)).
+fn signed_digits(scalar: &Scalar, w: usize) -> Vec<i32> {
+  let bytes = scalar.as_bytes();
+  let radix = 1i32 << w;
+  let count = 256usize.div_ceil(w) + 1;
+  let mut digits: Vec<i32> = Vec::with_capacity(count);
+  let mut carry = 0;
+  for i in 0..count {
+    let mut bits = 0;
+    for j in 0..w {
+      let bit = i * w + j;
+      if bit < 256 && (bytes[bit / 8] >> (bit % 8)) & 1 == 1 {
+        bits |= 1 << j;
+      }
+    }
+    let value = bits + carry;
+    if value >= radix / 2 {
+      digits.push(value - radix);
+      carry = 1;
+    } else {
+      digits.push(value);
+      carry = 0;
+    }
+  }
+  digits
+}
+
+fn add_digit(acc: &Point, table: &[GeCached], digit: i32) -> Point {
+  if digit > 0 {
+    acc.add_cached(&table[(digit - 1) as usize])
+  } else if digit < 0 {
+    acc.sub_cached(&table[(-digit - 1) as usize])
+  } else {
+    *acc
+  }
+}
+
+impl MultiExp {
+  // sum(scalars[i] * points[i]), variable time so only for public data such
+  // as signature verification.
+  pub fn compute(scalars: &[Scalar], points: &[Point]) -> Point {
+    if scalars.len() < STRAUS_THRESHOLD {
+      MultiExp::straus(scalars, points)
+    } else {
+      MultiExp::pippenger(scalars, points)
+    }
+  }
+
+  pub fn straus(scalars: &[Scalar], points: &[Point]) -> Point {
+    assert!(scalars.len() == points.len());
+    const WINDOW: usize = 4;
+    let tables: Vec<Vec<GeCached>> = points
+      .iter()
+      .map(|point| {
+        let mut multiples = vec![point.cached()];
+        let mut current = *point;
+        for _ in 1..1 << (WINDOW - 1) {
+          current = current + point;
+          multiples.push(current.cached());
+        }
+        multiples
+      })
+      .collect();
+    let digits: Vec<Vec<i32>> = scalars.iter().map(|s| signed_digits(s, WINDOW)).collect();
+
+    let mut acc = Point::identity();
+    for i in (0..digits.first().map_or(0, |d| d.len())).rev() {
+      acc = acc.mul_by_pow_2(WINDOW as u32);
+      for (table, d) in tables.iter().zip(digits.iter()) {
+        acc = add_digit(&acc, table, d[i]);
+      }
+    }
+    acc
+  }
+
+  pub fn pippenger(scalars: &[Scalar], points: &[Point]) -> Point {
+    assert!(scalars.len() == points.len());
+    let window = match scalars.len() {
+      0..=31 => 3,
+      32..=191 => 5,
+      192..=767 => 6,
+      768..=2047 => 7,
+      _ => 8,
+    };
+    let cached: Vec<GeCached> = points.iter().map(|p| p.cached()).collect();
+    let digits: Vec<Vec<i32>> = scalars.iter().map(|s| signed_digits(s, window)).collect();
+    let buckets_count = 1 << (window - 1);
+
+    let mut acc = Point::identity();
+    for i in (0..digits.first().map_or(0, |d| d.len())).rev() {
+      acc = acc.mul_by_pow_2(window as u32);
+      let mut buckets = vec![Point::identity(); buckets_count];
+      for (point, d) in cached.iter().zip(digits.iter()) {
+        let digit = d[i];
+        if digit > 0 {
+          let bucket = (digit - 1) as usize;
+          buckets[bucket] = buckets[bucket].add_cached(point);
+        } else if digit < 0 {
+          let bucket = (-digit - 1) as usize;
+          buckets[bucket] = buckets[bucket].sub_cached(point);
+        }
+      }
+      // sum(k * buckets[k - 1]) through running sums
+      let mut running = Point::identity();
+      let mut total = Point::identity();
+      for bucket in buckets.iter().rev() {
+        running = running + bucket;
+        total = total + running;
+      }
+      acc = acc + total;
+    }
+    acc
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn naive(scalars: &[Scalar], points: &[Point]) -> Point {
+    scalars
+      .iter()
+      .zip(points.iter())
+      .fold(Point::identity(), |acc, (s, p)| acc + s * p)
+  }
+
+  fn terms(n: usize) -> (Vec<Scalar>, Vec<Point>) {
+    let scalars: Vec<Scalar> = (0..n).map(|i| Scalar::hash(&(i as u64).to_le_bytes())).collect();
+    let points: Vec<Point> = (0..n)
+      .map(|i| Point::hash_to_point(&(i as u64 + 1000).to_le_bytes()))
+      .collect();
+    (scalars, points)
+  }
+
+  #[test]
+  fn should_decompose_signed_digits() {
+    for w in 2..9 {
+      let scalar = Scalar::hash(b"digits");
+      let digits = signed_digits(&scalar, w);
+      let radix = Scalar::from(1u64 << w);
+      let mut value = Scalar::zero();
+      for digit in digits.iter().rev() {
+        let d = if *digit < 0 {
+          -Scalar::from((-digit) as u64)
+        } else {
+          Scalar::from(*digit as u64)
+        };
+        value = value * radix + d;
+      }
+      assert!(value == scalar);
+    }
+  }
+
+  #[test]
+  fn should_match_naive_multiplication() {
+    for n in &[0, 1, 2, 7, 33, 70] {
+      let (scalars, points) = terms(*n);
+      let expected = naive(&scalars, &points);
+      assert!(MultiExp::straus(&scalars, &points) == expected);
+      assert!(MultiExp::pippenger(&scalars, &points) == expected);
+      assert!(MultiExp::compute(&scalars, &points) == expected);
+    }
+    let (mut scalars, points) = terms(3);
+    scalars[1] = Scalar::zero();
+    scalars[2] = -Scalar::one();
+    assert!(MultiExp::compute(&scalars, &points) == scalars[0] * points[0] - points[2]);
+  }
+}
@@ -0,0 +1,649 @@
+use super::hash::Hash;
+use super::key::Key;
+use super::ring::RingSignature;
+use super::scalar::{EllipticCurvePoint, EllipticCurveScalar};
+use rand_core::{CryptoRng, RngCore};
+use std::collections::BTreeMap;
+
+const KEY_DERIVATION_TAG: &[u8] = b"multisig";
+
+#[derive(Debug, PartialEq)]
+pub enum MultisigError {
+  InvalidThreshold,
+  InvalidIndex,
+  InvalidSecretKey,
+  InvalidPublicKey,
+  InvalidSignature,
+  UnexpectedRound,
+  UnexpectedSender,
+  DuplicateSender,
+  MissingKey,
+  ConflictingKey,
+  NotReady,
+  InvalidSigners,
+  InvalidRing,
+}
+
+// Keys shared by a subset of participants are identified by the sorted
+// indices of their holders.
+pub type KeyOwners = Vec<usize>;
+
+pub struct KeyExchangeMessage {
+  pub round: usize,
+  pub sender: usize,
+  pub view_secret_key: Option<[u8; 32]>,
+  pub public_keys: Vec<(KeyOwners, [u8; 32])>,
+  pub signature: [u8; 64],
+}
+
+pub struct PartialKeyImage {
+  pub owners: KeyOwners,
+  pub image: [u8; 32],
+}
+
+pub struct SigningNonce {
+  pub sender: usize,
+  secret: [u8; 32],
+  pub commitment: NonceCommitment,
+}
+
+#[derive(Clone)]
+pub struct NonceCommitment {
+  pub sender: usize,
+  pub base: [u8; 32],
+  pub image: [u8; 32],
+}
+
+pub struct PartialRingSignature {
+  pub signature: RingSignature,
+  pub sec_index: usize,
+  pub challenge: [u8; 32],
+}
+
+pub struct Multisig {
+  threshold: usize,
+  participants: usize,
+  index: usize,
+  round: usize,
+  spend_secret_key: [u8; 32],
+  view_secret_key: [u8; 32],
+  base_public_keys: Vec<Option<[u8; 32]>>,
+  secret_keys: BTreeMap<KeyOwners, [u8; 32]>,
+  spend_public_key: Option<[u8; 32]>,
+}
+
+fn combinations(n: usize, k: usize) -> Vec<KeyOwners> {
+  let mut result: Vec<KeyOwners> = vec![];
+  let mut current: KeyOwners = vec![];
+  fn walk(start: usize, n: usize, k: usize, current: &mut KeyOwners, result: &mut Vec<KeyOwners>) {
+    if current.len() == k {
+      result.push(current.clone());
+      return;
+    }
+    for i in start..n {
+      current.push(i);
+      walk(i + 1, n, k, current, result);
+      current.pop();
+    }
+  }
+  walk(0, n, k, &mut current, &mut result);
+  result
+}
+
+fn message_hash(round: usize, sender: usize, view: &Option<[u8; 32]>, keys: &[(KeyOwners, [u8; 32])]) -> [u8; 32] {
+  let mut data: Vec<u8> = vec![];
+  data.extend_from_slice(KEY_DERIVATION_TAG);
+  data.extend_from_slice(&(round as u64).to_le_bytes());
+  data.extend_from_slice(&(sender as u64).to_le_bytes());
+  if let Some(view) = view {
+    data.extend_from_slice(view);
+  }
+  for (owners, key) in keys {
+    data.extend_from_slice(&(owners.len() as u64).to_le_bytes());
+    for owner in owners {
+      data.extend_from_slice(&(*owner as u64).to_le_bytes());
+    }
+    data.extend_from_slice(key);
+  }
+  Hash::fast(&data)
+}
+
+fn shared_secret(secret: &[u8; 32], public: &[u8; 32]) -> Option<[u8; 32]> {
+  let mut data: Vec<u8> = EllipticCurvePoint::scalar_mult(secret, public)?.to_vec();
+  data.extend_from_slice(KEY_DERIVATION_TAG);
+  Some(EllipticCurveScalar::to_hash(&data))
+}
+
+impl Multisig {
+  pub fn new(
+    threshold: usize,
+    participants: usize,
+    index: usize,
+    spend_secret_key: &[u8; 32],
+    view_secret_key: &[u8; 32],
+  ) -> Result<Multisig, MultisigError> {
+    if threshold < 1 || threshold > participants {
+      return Err(MultisigError::InvalidThreshold);
+    }
+    if index >= participants {
+      return Err(MultisigError::InvalidIndex);
+    }
+    if !EllipticCurveScalar::check(spend_secret_key) || !EllipticCurveScalar::check(view_secret_key) {
+      return Err(MultisigError::InvalidSecretKey);
+    }
+    let mut base_public_keys = vec![None; participants];
+    base_public_keys[index] = Some(EllipticCurvePoint::scalar_mult_base(spend_secret_key));
+    let mut secret_keys = BTreeMap::new();
+    secret_keys.insert(vec![index], *spend_secret_key);
+    Ok(Multisig {
+      threshold,
+      participants,
+      index,
+      round: 1,
+      spend_secret_key: *spend_secret_key,
+      view_secret_key: *view_secret_key,
+      base_public_keys,
+      secret_keys,
+      spend_public_key: None,
+    })
+  }
+
+  // Every key is shared by N - M + 1 participants so that any M of them
+  // hold all of the keys, one exchange round per key size.
+  pub fn rounds(&self) -> usize {
+    self.participants - self.threshold + 1
+  }
+
+  pub fn round(&self) -> usize {
+    self.round
+  }
+
+  pub fn index(&self) -> usize {
+    self.index
+  }
+
+  pub fn is_ready(&self) -> bool {
+    self.spend_public_key.is_some()
+  }
+
+  pub fn spend_public_key(&self) -> Option<[u8; 32]> {
+    self.spend_public_key
+  }
+
+  pub fn view_secret_key(&self) -> Option<[u8; 32]> {
+    if self.is_ready() {
+      Some(self.view_secret_key)
+    } else {
+      None
+    }
+  }
+
+  pub fn exchange_message(&self) -> KeyExchangeMessage {
+    self.exchange_message_with_rng(&mut rand::thread_rng())
+  }
+
+  pub fn exchange_message_with_rng<R: RngCore + CryptoRng>(&self, rng: &mut R) -> KeyExchangeMessage {
+    let public_keys: Vec<(KeyOwners, [u8; 32])> = self
+      .secret_keys
+      .iter()
+      .map(|(owners, secret)| (owners.clone(), EllipticCurvePoint::scalar_mult_base(secret)))
+      .collect();
+    let view_secret_key = if self.round == 1 {
+      Some(self.view_secret_key)
+    } else {
+      None
+    };
+    let hash = message_hash(self.round, self.index, &view_secret_key, &public_keys);
+    let signature = Key::generate_signature_with_rng(
+      &hash,
+      &self.base_public_keys[self.index].unwrap(),
+      &self.spend_secret_key,
+      rng,
+    );
+    KeyExchangeMessage {
+      round: self.round,
+      sender: self.index,
+      view_secret_key,
+      public_keys,
+      signature,
+    }
+  }
+
+  pub fn process_messages(&mut self, messages: &[KeyExchangeMessage]) -> Result<(), MultisigError> {
+    if self.is_ready() {
+      return Err(MultisigError::UnexpectedRound);
+    }
+    let mut publics: BTreeMap<KeyOwners, [u8; 32]> = BTreeMap::new();
+    let mut senders = vec![false; self.participants];
+    senders[self.index] = true;
+    for message in messages.iter().filter(|m| m.sender != self.index) {
+      self.verify_message(message)?;
+      if senders[message.sender] {
+        return Err(MultisigError::DuplicateSender);
+      }
+      senders[message.sender] = true;
+      for (owners, key) in &message.public_keys {
+        match publics.get(owners) {
+          Some(existing) if existing != key => return Err(MultisigError::ConflictingKey),
+          _ => {
+            publics.insert(owners.clone(), *key);
+          }
+        }
+      }
+    }
+    if senders.iter().any(|s| !s) {
+      return Err(MultisigError::MissingKey);
+    }
+    for (owners, secret) in &self.secret_keys {
+      let public = EllipticCurvePoint::scalar_mult_base(secret);
+      match publics.get(owners) {
+        Some(existing) if *existing != public => return Err(MultisigError::ConflictingKey),
+        _ => {
+          publics.insert(owners.clone(), public);
+        }
+      }
+    }
+
+    if self.round == 1 {
+      let index = self.index;
+      for message in messages.iter().filter(|m| m.sender != index) {
+        let view = message.view_secret_key.unwrap();
+        self.view_secret_key = EllipticCurveScalar::add(&self.view_secret_key, &view);
+        self.base_public_keys[message.sender] = Some(message.public_keys[0].1);
+      }
+    }
+
+    if self.round == self.rounds() {
+      let mut spend: Option<[u8; 32]> = None;
+      for owners in combinations(self.participants, self.round) {
+        let key = publics.get(&owners).ok_or(MultisigError::MissingKey)?;
+        spend = match spend {
+          None => Some(*key),
+          Some(sum) => Some(EllipticCurvePoint::add(&sum, key).ok_or(MultisigError::InvalidPublicKey)?),
+        };
+      }
+      self.spend_public_key = spend;
+      return Ok(());
+    }
+
+    // A key shared by `owners` is derived from the key of all but its last
+    // owner combined with the base key of the last owner.
+    let mut next: BTreeMap<KeyOwners, [u8; 32]> = BTreeMap::new();
+    for owners in combinations(self.participants, self.round + 1) {
+      if !owners.contains(&self.index) {
+        continue;
+      }
+      let last = owners[owners.len() - 1];
+      let rest: KeyOwners = owners[..owners.len() - 1].to_vec();
+      let secret = if last == self.index {
+        let public = publics.get(&rest).ok_or(MultisigError::MissingKey)?;
+        shared_secret(&self.spend_secret_key, public)
+      } else {
+        let secret = self.secret_keys.get(&rest).ok_or(MultisigError::MissingKey)?;
+        let public = self.base_public_keys[last].ok_or(MultisigError::MissingKey)?;
+        shared_secret(secret, &public)
+      };
+      next.insert(owners, secret.ok_or(MultisigError::InvalidPublicKey)?);
+    }
+    self.secret_keys = next;
+    self.round += 1;
+    Ok(())
+  }
+
+  fn verify_message(&self, message: &KeyExchangeMessage) -> Result<(), MultisigError> {
+    if message.round != self.round {
+      return Err(MultisigError::UnexpectedRound);
+    }
+    if message.sender >= self.participants {
+      return Err(MultisigError::UnexpectedSender);
+    }
+    for (owners, key) in &message.public_keys {
+      if owners.len() != self.round || !owners.contains(&message.sender) {
+        return Err(MultisigError::UnexpectedSender);
+      }
+      if !Key::check_public_key(key) {
+        return Err(MultisigError::InvalidPublicKey);
+      }
+    }
+    let signer = if self.round == 1 {
+      match (&message.view_secret_key, message.public_keys.as_slice()) {
+        (Some(view), [(owners, key)]) if owners[0] == message.sender => {
+          if !EllipticCurveScalar::check(view) {
+            return Err(MultisigError::InvalidSecretKey);
+          }
+          *key
+        }
+        _ => return Err(MultisigError::MissingKey),
+      }
+    } else {
+      self.base_public_keys[message.sender].ok_or(MultisigError::MissingKey)?
+    };
+    let hash = message_hash(message.round, message.sender, &message.view_secret_key, &message.public_keys);
+    if !Key::check_signature(&hash, &signer, &message.signature) {
+      return Err(MultisigError::InvalidSignature);
+    }
+    Ok(())
+  }
+
+  fn check_signers(&self, signers: &[usize]) -> Result<(), MultisigError> {
+    if !self.is_ready() {
+      return Err(MultisigError::NotReady);
+    }
+    if signers.len() < self.threshold
+      || signers.windows(2).any(|w| w[0] >= w[1])
+      || signers.iter().any(|s| *s >= self.participants)
+      || !signers.contains(&self.index)
+    {
+      return Err(MultisigError::InvalidSigners);
+    }
+    Ok(())
+  }
+
+  // The share of the spend secret key this participant signs with: every key
+  // is used by its lowest indexed owner among the signers, and the first
+  // signer also adds the output's derivation scalar.
+  fn secret_share(&self, signers: &[usize], derivation_scalar: &[u8; 32]) -> [u8; 32] {
+    let mut share: [u8; 32] = [0; 32];
+    if signers[0] == self.index {
+      share = *derivation_scalar;
+    }
+    for (owners, secret) in &self.secret_keys {
+      if owners.iter().find(|o| signers.contains(o)) == Some(&self.index) {
+        share = EllipticCurveScalar::add(&share, secret);
+      }
+    }
+    share
+  }
+
+  pub fn partial_key_images(&self, public_key: &[u8; 32]) -> Result<Vec<PartialKeyImage>, MultisigError> {
+    if !self.is_ready() {
+      return Err(MultisigError::NotReady);
+    }
+    let images = self
+      .secret_keys
+      .iter()
+      .map(|(owners, secret)| PartialKeyImage {
+        owners: owners.clone(),
+        image: Key::generate_partial_key_image(public_key, secret),
+      })
+      .collect();
+    Ok(images)
+  }
+
+  pub fn combine_key_images(
+    &self,
+    public_key: &[u8; 32],
+    derivation_scalar: &[u8; 32],
+    partials: &[PartialKeyImage],
+  ) -> Result<[u8; 32], MultisigError> {
+    if !self.is_ready() {
+      return Err(MultisigError::NotReady);
+    }
+    let mut images = vec![Key::generate_partial_key_image(public_key, derivation_scalar)];
+    for owners in combinations(self.participants, self.rounds()) {
+      let partial = partials
+        .iter()
+        .find(|p| p.owners == owners)
+        .ok_or(MultisigError::MissingKey)?;
+      images.push(partial.image);
+    }
+    Key::combine_key_images(&images).ok_or(MultisigError::InvalidPublicKey)
+  }
+
+  pub fn generate_nonce(&self, public_key: &[u8; 32]) -> SigningNonce {
+    self.generate_nonce_with_rng(public_key, &mut rand::thread_rng())
+  }
+
+  pub fn generate_nonce_with_rng<R: RngCore + CryptoRng>(&self, public_key: &[u8; 32], rng: &mut R) -> SigningNonce {
+    let mut secret: [u8; 32] = [0; 32];
+    EllipticCurveScalar::random_with_rng(&mut secret, rng);
+    let base = EllipticCurvePoint::scalar_mult_base(&secret);
+    let image = Key::generate_partial_key_image(public_key, &secret);
+    SigningNonce {
+      sender: self.index,
+      secret,
+      commitment: NonceCommitment {
+        sender: self.index,
+        base,
+        image,
+      },
+    }
+  }
+
+  // Builds the ring signature around the aggregated nonce of the signers,
+  // leaving the response of the real input to be filled by `combine`.
+  pub fn prepare_ring_signature(
+    prefix_hash: &[u8; 32],
+    image: &[u8; 32],
+    pubs: &[[u8; 32]],
+    sec_index: usize,
+    commitments: &[NonceCommitment],
+  ) -> Result<PartialRingSignature, MultisigError> {
+    Multisig::prepare_ring_signature_with_rng(prefix_hash, image, pubs, sec_index, commitments, &mut rand::thread_rng())
+  }
+
+  pub fn prepare_ring_signature_with_rng<R: RngCore + CryptoRng>(
+    prefix_hash: &[u8; 32],
+    image: &[u8; 32],
+    pubs: &[[u8; 32]],
+    sec_index: usize,
+    commitments: &[NonceCommitment],
+    rng: &mut R,
+  ) -> Result<PartialRingSignature, MultisigError> {
+    if sec_index >= pubs.len() || commitments.is_empty() {
+      return Err(MultisigError::InvalidRing);
+    }
+    let mut signature = RingSignature {
+      pairs: vec![([0; 32], [0; 32]); pubs.len()],
+    };
+    let mut buf: Vec<u8> = prefix_hash.to_vec();
+    let mut sum: [u8; 32] = [0; 32];
+    for (i, public_key) in pubs.iter().enumerate() {
+      let (a, b) = if i == sec_index {
+        let mut a = commitments[0].base;
+        let mut b = commitments[0].image;
+        for commitment in &commitments[1..] {
+          a = EllipticCurvePoint::add(&a, &commitment.base).ok_or(MultisigError::InvalidPublicKey)?;
+          b = EllipticCurvePoint::add(&b, &commitment.image).ok_or(MultisigError::InvalidPublicKey)?;
+        }
+        (a, b)
+      } else {
+        let mut c: [u8; 32] = [0; 32];
+        let mut r: [u8; 32] = [0; 32];
+        EllipticCurveScalar::random_with_rng(&mut c, rng);
+        EllipticCurveScalar::random_with_rng(&mut r, rng);
+        let a = EllipticCurvePoint::double_scalar_mult_base(&c, public_key, &r).ok_or(MultisigError::InvalidRing)?;
+        let hp = EllipticCurveScalar::from_hash(public_key);
+        let rh = EllipticCurvePoint::scalar_mult(&r, &hp).ok_or(MultisigError::InvalidRing)?;
+        let ci = EllipticCurvePoint::scalar_mult(&c, image).ok_or(MultisigError::InvalidRing)?;
+        let b = EllipticCurvePoint::add(&rh, &ci).ok_or(MultisigError::InvalidRing)?;
+        signature.pairs[i] = (c, r);
+        sum = EllipticCurveScalar::add(&sum, &c);
+        (a, b)
+      };
+      buf.extend_from_slice(&a);
+      buf.extend_from_slice(&b);
+    }
+    let h = EllipticCurveScalar::to_hash(&buf);
+    let challenge = EllipticCurveScalar::sub(&h, &sum);
+    signature.pairs[sec_index].0 = challenge;
+    Ok(PartialRingSignature {
+      signature,
+      sec_index,
+      challenge,
+    })
+  }
+
+  pub fn sign_partial(
+    &self,
+    partial: &PartialRingSignature,
+    nonce: &SigningNonce,
+    signers: &[usize],
+    derivation_scalar: &[u8; 32],
+  ) -> Result<[u8; 32], MultisigError> {
+    self.check_signers(signers)?;
+    if nonce.sender != self.index {
+      return Err(MultisigError::UnexpectedSender);
+    }
+    let share = self.secret_share(signers, derivation_scalar);
+    Ok(EllipticCurveScalar::mul_sub(&partial.challenge, &share, &nonce.secret))
+  }
+
+  pub fn combine_ring_signature(partial: &PartialRingSignature, responses: &[[u8; 32]]) -> RingSignature {
+    let mut response: [u8; 32] = [0; 32];
+    for r in responses {
+      response = EllipticCurveScalar::add(&response, r);
+    }
+    let mut signature = partial.signature.clone();
+    signature.pairs[partial.sec_index].1 = response;
+    signature
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::ring::Ring;
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+
+  fn setup(threshold: usize, participants: usize) -> Vec<Multisig> {
+    let mut accounts: Vec<Multisig> = (0..participants)
+      .map(|i| {
+        let spend = Key::generate_secret_key();
+        let view = Key::generate_secret_key();
+        Multisig::new(threshold, participants, i, &spend, &view).unwrap()
+      })
+      .collect();
+    while !accounts[0].is_ready() {
+      let messages: Vec<KeyExchangeMessage> = accounts.iter().map(|a| a.exchange_message()).collect();
+      for account in accounts.iter_mut() {
+        account.process_messages(&messages).unwrap();
+      }
+    }
+    accounts
+  }
+
+  fn full_secret(accounts: &[Multisig]) -> [u8; 32] {
+    let mut all: BTreeMap<KeyOwners, [u8; 32]> = BTreeMap::new();
+    for account in accounts {
+      for (owners, secret) in &account.secret_keys {
+        all.insert(owners.clone(), *secret);
+      }
+    }
+    let mut sum: [u8; 32] = [0; 32];
+    for secret in all.values() {
+      sum = EllipticCurveScalar::add(&sum, secret);
+    }
+    sum
+  }
+
+  fn sign_and_check(accounts: &[Multisig], signers: &[usize]) {
+    let spend_public = accounts[0].spend_public_key().unwrap();
+    let view_secret = accounts[0].view_secret_key().unwrap();
+
+    let mut tx_public: [u8; 32] = [0; 32];
+    let mut tx_secret: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut tx_public, &mut tx_secret);
+    let derivation = Key::generate_key_derivation(&tx_public, &view_secret);
+    let output_key = Key::derive_public_key(&derivation, 1, &spend_public);
+    let derivation_scalar = Key::derive_secret_key(&derivation, 1, &[0; 32]);
+
+    let mut partials: Vec<PartialKeyImage> = vec![];
+    for i in signers {
+      partials.extend(accounts[*i].partial_key_images(&output_key).unwrap());
+    }
+    let image = accounts[signers[0]]
+      .combine_key_images(&output_key, &derivation_scalar, &partials)
+      .unwrap();
+    let secret = EllipticCurveScalar::add(&derivation_scalar, &full_secret(accounts));
+    let mut public: [u8; 32] = [0; 32];
+    Key::secret_to_public(&secret, &mut public);
+    assert!(public == output_key);
+    assert!(image == Key::generate_key_image(&output_key, &secret));
+
+    let prefix_hash = Hash::fast(b"multisig transaction");
+    let mut pubs: Vec<[u8; 32]> = vec![];
+    for _ in 0..3 {
+      let mut decoy: [u8; 32] = [0; 32];
+      let mut decoy_secret: [u8; 32] = [0; 32];
+      Key::generate_key_pair(&mut decoy, &mut decoy_secret);
+      pubs.push(decoy);
+    }
+    pubs.insert(2, output_key);
+
+    let nonces: Vec<SigningNonce> = signers.iter().map(|i| accounts[*i].generate_nonce(&output_key)).collect();
+    let commitments: Vec<NonceCommitment> = nonces.iter().map(|n| n.commitment.clone()).collect();
+    let partial = Multisig::prepare_ring_signature(&prefix_hash, &image, &pubs, 2, &commitments).unwrap();
+    let responses: Vec<[u8; 32]> = signers
+      .iter()
+      .zip(nonces.iter())
+      .map(|(i, nonce)| {
+        accounts[*i]
+          .sign_partial(&partial, nonce, signers, &derivation_scalar)
+          .unwrap()
+      })
+      .collect();
+    let signature = Multisig::combine_ring_signature(&partial, &responses);
+    assert!(Ring::check_signature(&prefix_hash, &image, &pubs, &signature));
+
+    let incomplete = Multisig::combine_ring_signature(&partial, &responses[1..]);
+    assert!(!Ring::check_signature(&prefix_hash, &image, &pubs, &incomplete));
+  }
+
+  #[test]
+  fn should_agree_on_multisig_keys() {
+    let accounts = setup(2, 3);
+    assert!(accounts[0].rounds() == 2);
+    let spend = accounts[0].spend_public_key().unwrap();
+    let view = accounts[0].view_secret_key().unwrap();
+    for account in &accounts {
+      assert!(account.spend_public_key().unwrap() == spend);
+      assert!(account.view_secret_key().unwrap() == view);
+    }
+    let mut public: [u8; 32] = [0; 32];
+    Key::secret_to_public(&full_secret(&accounts), &mut public);
+    assert!(public == spend);
+  }
+
+  #[test]
+  fn should_sign_with_n_of_n() {
+    let accounts = setup(2, 2);
+    sign_and_check(&accounts, &[0, 1]);
+  }
+
+  #[test]
+  fn should_sign_with_m_of_n() {
+    let accounts = setup(2, 3);
+    sign_and_check(&accounts, &[0, 1]);
+    sign_and_check(&accounts, &[0, 2]);
+    sign_and_check(&accounts, &[1, 2]);
+
+    let accounts = setup(2, 4);
+    assert!(accounts[0].rounds() == 3);
+    sign_and_check(&accounts, &[1, 3]);
+  }
+
+  #[test]
+  fn should_reject_bad_exchange_messages() {
+    assert!(Multisig::new(3, 2, 0, &[1; 32], &[1; 32]).err() == Some(MultisigError::InvalidThreshold));
+    assert!(Multisig::new(2, 2, 2, &[1; 32], &[1; 32]).err() == Some(MultisigError::InvalidIndex));
+
+    let mut accounts: Vec<Multisig> = (0..3)
+      .map(|i| Multisig::new(2, 3, i, &Key::generate_secret_key(), &Key::generate_secret_key()).unwrap())
+      .collect();
+    let mut messages: Vec<KeyExchangeMessage> = accounts.iter().map(|a| a.exchange_message()).collect();
+    messages[1].public_keys[0].1 = messages[2].public_keys[0].1;
+    assert!(accounts[0].process_messages(&messages).err() == Some(MultisigError::InvalidSignature));
+
+    let messages: Vec<KeyExchangeMessage> = accounts
+      .iter()
+      .map(|a| a.exchange_message_with_rng(&mut StdRng::seed_from_u64(7)))
+      .collect();
+    assert!(messages[0].signature == accounts[0].exchange_message_with_rng(&mut StdRng::seed_from_u64(7)).signature);
+    assert!(accounts[0].process_messages(&messages[..2]).err() == Some(MultisigError::MissingKey));
+    accounts[0].process_messages(&messages).unwrap();
+    assert!(accounts[0].process_messages(&messages).err() == Some(MultisigError::UnexpectedRound));
+    assert!(accounts[0].partial_key_images(&[0; 32]).err() == Some(MultisigError::NotReady));
+  }
+}
@@ -0,0 +1,707 @@
+use super::base58::Base58;
+use super::commitment::Commitment;
+use super::hash::Hash;
+use super::key::Key;
+use super::ring::{Ring, RingError, RingSignature};
+use super::scalar::{Point, Scalar};
+use rand_core::{CryptoRng, RngCore};
+
+const TX_PROOF_DOMAIN: &[u8] = b"TXPROOF_V2";
+const OUT_PROOF_HEADER: &str = "OutProofV2";
+const IN_PROOF_HEADER: &str = "InProofV2";
+// base58 of a 32 byte shared secret and of a 64 byte signature
+const ENCODED_KEY_SIZE: usize = 44;
+const ENCODED_SIGNATURE_SIZE: usize = 88;
+const RESERVE_PROOF_DOMAIN: &[u8] = b"ReserveProofV1";
+const SPEND_PROOF_HEADER: &str = "SpendProofV1";
+
+#[derive(Debug, PartialEq)]
+pub enum ProofError {
+  InvalidHeader,
+  InvalidEncoding,
+  InvalidPublicKey,
+  InvalidSecretKey,
+  KeyCountMismatch,
+  SecretKeyMismatch,
+  Ring(RingError),
+}
+
+// One (D, signature) pair per transaction public key, where D is the shared
+// secret r * A that the recipient sees as a * R.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxProof {
+  pub outbound: bool,
+  pub entries: Vec<([u8; 32], [u8; 64])>,
+}
+
+fn point(bytes: &[u8; 32]) -> Result<Point, ProofError> {
+  Point::from_bytes(bytes).ok_or(ProofError::InvalidPublicKey)
+}
+
+// H_s(prefix || D || X || Y || H("TXPROOF_V2") || R || A || B), B being zero
+// for a standard address.
+fn tx_proof_challenge(
+  prefix_hash: &[u8; 32],
+  r: &Point,
+  a: &Point,
+  b: Option<&Point>,
+  d: &Point,
+  x: &Point,
+  y: &Point,
+) -> Scalar {
+  let mut data = prefix_hash.to_vec();
+  for p in &[d, x, y] {
+    data.extend_from_slice(&p.to_bytes());
+  }
+  data.extend_from_slice(&Hash::fast(TX_PROOF_DOMAIN));
+  data.extend_from_slice(&r.to_bytes());
+  data.extend_from_slice(&a.to_bytes());
+  data.extend_from_slice(&b.map_or([0; 32], |b| b.to_bytes()));
+  Scalar::hash(&data)
+}
+
+// Proves knowledge of s with R = s * G (or s * B) and D = s * A.
+fn generate_tx_signature<R: RngCore + CryptoRng>(
+  prefix_hash: &[u8; 32],
+  r: &Point,
+  a: &Point,
+  b: Option<&Point>,
+  d: &Point,
+  secret: &Scalar,
+  rng: &mut R,
+) -> [u8; 64] {
+  let k = Scalar::random_with_rng(rng);
+  let x = match b {
+    Some(b) => k * b,
+    None => Point::mul_base(&k),
+  };
+  let y = k * a;
+  let c = tx_proof_challenge(prefix_hash, r, a, b, d, &x, &y);
+  let mut signature: [u8; 64] = [0; 64];
+  signature[..32].copy_from_slice(c.as_bytes());
+  signature[32..].copy_from_slice(c.mul_sub(secret, &k).as_bytes());
+  signature
+}
+
+fn check_tx_signature(
+  prefix_hash: &[u8; 32],
+  r: &Point,
+  a: &Point,
+  b: Option<&Point>,
+  d: &Point,
+  signature: &[u8; 64],
+) -> bool {
+  let mut c: [u8; 32] = [0; 32];
+  let mut s: [u8; 32] = [0; 32];
+  c.copy_from_slice(&signature[..32]);
+  s.copy_from_slice(&signature[32..]);
+  let (c, s) = match (Scalar::from_canonical_bytes(&c), Scalar::from_canonical_bytes(&s)) {
+    (Some(c), Some(s)) => (c, s),
+    _ => return false,
+  };
+  let x = match b {
+    Some(b) => c * r + s * b,
+    None => Point::double_mul_base(&c, r, &s),
+  };
+  let y = c * d + s * a;
+  tx_proof_challenge(prefix_hash, r, a, b, d, &x, &y) == c
+}
+
+impl TxProof {
+  // The message signed by a tx proof, H(txid || message).
+  pub fn prefix_hash(txid: &[u8; 32], message: &[u8]) -> [u8; 32] {
+    let mut data = txid.to_vec();
+    data.extend_from_slice(message);
+    Hash::fast(&data)
+  }
+
+  // Proof by the sender, who knows the transaction secret keys r_i, that the
+  // transaction paid the address (A, B). spend_public_key is Some for a
+  // subaddress, whose transaction public keys are r_i * B instead of r_i * G.
+  pub fn generate_outbound(
+    txid: &[u8; 32],
+    message: &[u8],
+    tx_secret_keys: &[[u8; 32]],
+    view_public_key: &[u8; 32],
+    spend_public_key: Option<&[u8; 32]>,
+  ) -> Result<TxProof, ProofError> {
+    TxProof::generate_outbound_with_rng(
+      txid,
+      message,
+      tx_secret_keys,
+      view_public_key,
+      spend_public_key,
+      &mut rand::thread_rng(),
+    )
+  }
+
+  pub fn generate_outbound_with_rng<R: RngCore + CryptoRng>(
+    txid: &[u8; 32],
+    message: &[u8],
+    tx_secret_keys: &[[u8; 32]],
+    view_public_key: &[u8; 32],
+    spend_public_key: Option<&[u8; 32]>,
+    rng: &mut R,
+  ) -> Result<TxProof, ProofError> {
+    let prefix_hash = TxProof::prefix_hash(txid, message);
+    let a = point(view_public_key)?;
+    let b = match spend_public_key {
+      Some(b) => Some(point(b)?),
+      None => None,
+    };
+    let mut entries = vec![];
+    for secret in tx_secret_keys {
+      let r = Scalar::from_canonical_bytes(secret).ok_or(ProofError::InvalidSecretKey)?;
+      let tx_public_key = match &b {
+        Some(b) => r * b,
+        None => Point::mul_base(&r),
+      };
+      let d = r * a;
+      let signature = generate_tx_signature(&prefix_hash, &tx_public_key, &a, b.as_ref(), &d, &r, rng);
+      entries.push((d.to_bytes(), signature));
+    }
+    Ok(TxProof { outbound: true, entries })
+  }
+
+  // Proof by the recipient, with the view secret key a, that the transaction
+  // with public keys R_i paid its address.
+  pub fn generate_inbound(
+    txid: &[u8; 32],
+    message: &[u8],
+    tx_public_keys: &[[u8; 32]],
+    view_secret_key: &[u8; 32],
+  ) -> Result<TxProof, ProofError> {
+    TxProof::generate_inbound_with_rng(txid, message, tx_public_keys, view_secret_key, &mut rand::thread_rng())
+  }
+
+  pub fn generate_inbound_with_rng<R: RngCore + CryptoRng>(
+    txid: &[u8; 32],
+    message: &[u8],
+    tx_public_keys: &[[u8; 32]],
+    view_secret_key: &[u8; 32],
+    rng: &mut R,
+  ) -> Result<TxProof, ProofError> {
+    let prefix_hash = TxProof::prefix_hash(txid, message);
+    let a = Scalar::from_canonical_bytes(view_secret_key).ok_or(ProofError::InvalidSecretKey)?;
+    let view_public_key = Point::mul_base(&a);
+    let mut entries = vec![];
+    for tx_public_key in tx_public_keys {
+      let r = point(tx_public_key)?;
+      let d = a * r;
+      // the roles of the two keys swap: a * G and D = a * R
+      let signature = generate_tx_signature(&prefix_hash, &view_public_key, &r, None, &d, &a, rng);
+      entries.push((d.to_bytes(), signature));
+    }
+    Ok(TxProof { outbound: false, entries })
+  }
+
+  pub fn verify(
+    &self,
+    txid: &[u8; 32],
+    message: &[u8],
+    tx_public_keys: &[[u8; 32]],
+    view_public_key: &[u8; 32],
+    spend_public_key: Option<&[u8; 32]>,
+  ) -> bool {
+    if self.entries.is_empty() || self.entries.len() != tx_public_keys.len() {
+      return false;
+    }
+    let prefix_hash = TxProof::prefix_hash(txid, message);
+    let keys: Option<Vec<Point>> = tx_public_keys.iter().map(Point::from_bytes).collect();
+    let a = Point::from_bytes(view_public_key);
+    let b = spend_public_key.map(Point::from_bytes);
+    let (keys, a) = match (keys, a, b) {
+      (Some(keys), Some(a), None) | (Some(keys), Some(a), Some(Some(_))) => (keys, a),
+      _ => return false,
+    };
+    let b = b.and_then(|b| b);
+    self.entries.iter().zip(keys.iter()).all(|((d, signature), r)| {
+      let d = match Point::from_bytes(d) {
+        Some(d) => d,
+        None => return false,
+      };
+      if self.outbound {
+        check_tx_signature(&prefix_hash, r, &a, b.as_ref(), &d, signature)
+      } else {
+        spend_public_key.is_none() && check_tx_signature(&prefix_hash, &a, r, None, &d, signature)
+      }
+    })
+  }
+
+  // 8 * D for each entry, the key derivation with which the verifier finds
+  // and decodes the outputs paid to the address.
+  pub fn derivations(&self) -> Vec<[u8; 32]> {
+    self
+      .entries
+      .iter()
+      .filter_map(|(d, _)| Point::from_bytes(d))
+      .map(|d| d.mul_by_pow_2(3).to_bytes())
+      .collect()
+  }
+
+  // "OutProofV2" or "InProofV2" followed by base58(D) || base58(signature)
+  // for every entry.
+  pub fn encode(&self) -> String {
+    let mut encoded = String::from(if self.outbound {
+      OUT_PROOF_HEADER
+    } else {
+      IN_PROOF_HEADER
+    });
+    for (d, signature) in &self.entries {
+      encoded.push_str(&Base58::encode(d));
+      encoded.push_str(&Base58::encode(signature));
+    }
+    encoded
+  }
+
+  pub fn decode(encoded: &str) -> Result<TxProof, ProofError> {
+    let (outbound, body) = if let Some(body) = encoded.strip_prefix(OUT_PROOF_HEADER) {
+      (true, body)
+    } else if let Some(body) = encoded.strip_prefix(IN_PROOF_HEADER) {
+      (false, body)
+    } else {
+      return Err(ProofError::InvalidHeader);
+    };
+    let entry_size = ENCODED_KEY_SIZE + ENCODED_SIGNATURE_SIZE;
+    if body.is_empty() || !body.is_ascii() || body.len() % entry_size != 0 {
+      return Err(ProofError::InvalidEncoding);
+    }
+    let mut entries = vec![];
+    for i in (0..body.len()).step_by(entry_size) {
+      let d = Base58::decode(&body[i..i + ENCODED_KEY_SIZE]).ok_or(ProofError::InvalidEncoding)?;
+      let signature = Base58::decode(&body[i + ENCODED_KEY_SIZE..i + entry_size]).ok_or(ProofError::InvalidEncoding)?;
+      let mut entry = ([0; 32], [0; 64]);
+      entry.0.copy_from_slice(&d);
+      entry.1.copy_from_slice(&signature);
+      entries.push(entry);
+    }
+    Ok(TxProof { outbound, entries })
+  }
+}
+
+// An output of the wallet to put in a reserve proof: the one-time key pair
+// and the shared secret H_s(derivation || index) hiding its amount.
+pub struct OwnedOutput {
+  pub public_key: [u8; 32],
+  pub secret_key: [u8; 32],
+  pub shared_secret: [u8; 32],
+}
+
+// An output as the verifier reads it from the chain.
+pub struct ReserveOutput {
+  pub public_key: [u8; 32],
+  pub commitment: Commitment,
+  pub encrypted_amount: [u8; 8],
+}
+
+// The key image of an output with a ring signature of one member linking it
+// to the output key, and the shared secret that opens the output amount.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReserveEntry {
+  pub key_image: [u8; 32],
+  pub shared_secret: [u8; 32],
+  pub key_image_signature: RingSignature,
+}
+
+// Entries in the order of the outputs, and a signature of the whole proof by
+// the spend key of the address owning them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReserveProof {
+  pub entries: Vec<ReserveEntry>,
+  pub signature: [u8; 64],
+}
+
+impl ReserveProof {
+  // Hash of the message, the address and the (output key, key image, shared
+  // secret) of every entry, signed by each key image and the spend key, so
+  // entries cannot be moved to another proof.
+  fn prefix_hash(message: &[u8], spend_public_key: &[u8; 32], entries: &[([u8; 32], [u8; 32], [u8; 32])]) -> [u8; 32] {
+    let mut data = message.to_vec();
+    data.extend_from_slice(spend_public_key);
+    for (public_key, key_image, shared_secret) in entries {
+      data.extend_from_slice(public_key);
+      data.extend_from_slice(key_image);
+      data.extend_from_slice(shared_secret);
+    }
+    Key::message_hash(RESERVE_PROOF_DOMAIN, &data)
+  }
+
+  pub fn generate(
+    message: &[u8],
+    outputs: &[OwnedOutput],
+    spend_public_key: &[u8; 32],
+    spend_secret_key: &[u8; 32],
+  ) -> Result<ReserveProof, ProofError> {
+    ReserveProof::generate_with_rng(
+      message,
+      outputs,
+      spend_public_key,
+      spend_secret_key,
+      &mut rand::thread_rng(),
+    )
+  }
+
+  pub fn generate_with_rng<R: RngCore + CryptoRng>(
+    message: &[u8],
+    outputs: &[OwnedOutput],
+    spend_public_key: &[u8; 32],
+    spend_secret_key: &[u8; 32],
+    rng: &mut R,
+  ) -> Result<ReserveProof, ProofError> {
+    let mut public_key: [u8; 32] = [0; 32];
+    if !Key::secret_to_public(spend_secret_key, &mut public_key) || public_key != *spend_public_key {
+      return Err(ProofError::SecretKeyMismatch);
+    }
+    let parts: Vec<([u8; 32], [u8; 32], [u8; 32])> = outputs
+      .iter()
+      .map(|output| {
+        let key_image = Key::generate_key_image(&output.public_key, &output.secret_key);
+        (output.public_key, key_image, output.shared_secret)
+      })
+      .collect();
+    let prefix_hash = ReserveProof::prefix_hash(message, spend_public_key, &parts);
+    let mut entries = vec![];
+    for (output, (_, key_image, _)) in outputs.iter().zip(parts.iter()) {
+      let key_image_signature = Ring::generate_signature_with_rng(
+        &prefix_hash,
+        key_image,
+        &[output.public_key],
+        &output.secret_key,
+        0,
+        rng,
+      )
+      .map_err(|_| ProofError::SecretKeyMismatch)?;
+      entries.push(ReserveEntry {
+        key_image: *key_image,
+        shared_secret: output.shared_secret,
+        key_image_signature,
+      });
+    }
+    let signature = Key::generate_signature_with_rng(&prefix_hash, spend_public_key, spend_secret_key, rng);
+    Ok(ReserveProof { entries, signature })
+  }
+
+  // The total amount of the outputs, or None if any entry fails. Whether
+  // the key images are spent is for the caller to check against the chain.
+  pub fn verify(&self, message: &[u8], spend_public_key: &[u8; 32], outputs: &[ReserveOutput]) -> Option<u64> {
+    if self.entries.is_empty() || self.entries.len() != outputs.len() {
+      return None;
+    }
+    let parts: Vec<([u8; 32], [u8; 32], [u8; 32])> = outputs
+      .iter()
+      .zip(self.entries.iter())
+      .map(|(output, entry)| (output.public_key, entry.key_image, entry.shared_secret))
+      .collect();
+    let prefix_hash = ReserveProof::prefix_hash(message, spend_public_key, &parts);
+    if !Key::check_signature(&prefix_hash, spend_public_key, &self.signature) {
+      return None;
+    }
+    let mut total: u64 = 0;
+    for (i, (output, entry)) in outputs.iter().zip(self.entries.iter()).enumerate() {
+      if self.entries[..i].iter().any(|other| other.key_image == entry.key_image) {
+        return None;
+      }
+      if !Ring::check_signature(
+        &prefix_hash,
+        &entry.key_image,
+        &[output.public_key],
+        &entry.key_image_signature,
+      ) {
+        return None;
+      }
+      let amount = output.commitment.open(&entry.shared_secret, &output.encrypted_amount)?;
+      total = total.checked_add(amount)?;
+    }
+    Some(total)
+  }
+
+  pub fn key_images(&self) -> Vec<[u8; 32]> {
+    self.entries.iter().map(|entry| entry.key_image).collect()
+  }
+}
+
+// An input of a transaction sent by the wallet: its ring in order, the key
+// image, and the one-time secret key of the real member.
+pub struct SpendInput {
+  pub image: [u8; 32],
+  pub pubs: Vec<[u8; 32]>,
+  pub secret_key: [u8; 32],
+  pub real_index: usize,
+}
+
+// A new ring signature per input over H(txid || message), with the key image
+// of the input, so that only the sender could have made it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpendProof {
+  pub signatures: Vec<RingSignature>,
+}
+
+impl SpendProof {
+  pub fn generate(txid: &[u8; 32], message: &[u8], inputs: &[SpendInput]) -> Result<SpendProof, ProofError> {
+    SpendProof::generate_with_rng(txid, message, inputs, &mut rand::thread_rng())
+  }
+
+  pub fn generate_with_rng<R: RngCore + CryptoRng>(
+    txid: &[u8; 32],
+    message: &[u8],
+    inputs: &[SpendInput],
+    rng: &mut R,
+  ) -> Result<SpendProof, ProofError> {
+    let prefix_hash = TxProof::prefix_hash(txid, message);
+    let mut signatures = vec![];
+    for input in inputs {
+      let signature = Ring::generate_signature_with_rng(
+        &prefix_hash,
+        &input.image,
+        &input.pubs,
+        &input.secret_key,
+        input.real_index,
+        rng,
+      )
+      .map_err(ProofError::Ring)?;
+      signatures.push(signature);
+    }
+    Ok(SpendProof { signatures })
+  }
+
+  // inputs are the (key image, ring) pairs of the transaction, in order.
+  pub fn verify(&self, txid: &[u8; 32], message: &[u8], inputs: &[([u8; 32], Vec<[u8; 32]>)]) -> bool {
+    if inputs.is_empty() || self.signatures.len() != inputs.len() {
+      return false;
+    }
+    let prefix_hash = TxProof::prefix_hash(txid, message);
+    self
+      .signatures
+      .iter()
+      .zip(inputs.iter())
+      .all(|(signature, (image, pubs))| Ring::check_signature(&prefix_hash, image, pubs, signature))
+  }
+
+  // "SpendProofV1" followed by the base58 of every signature.
+  pub fn encode(&self) -> String {
+    let mut encoded = String::from(SPEND_PROOF_HEADER);
+    for signature in &self.signatures {
+      encoded.push_str(&Base58::encode(&signature.to_bytes()));
+    }
+    encoded
+  }
+
+  // A signature over n members is 64 * n bytes, so 88 * n characters.
+  pub fn decode(encoded: &str, ring_sizes: &[usize]) -> Result<SpendProof, ProofError> {
+    let body = encoded
+      .strip_prefix(SPEND_PROOF_HEADER)
+      .ok_or(ProofError::InvalidHeader)?;
+    let total: usize = ring_sizes.iter().map(|size| size * ENCODED_SIGNATURE_SIZE).sum();
+    if ring_sizes.is_empty() || !body.is_ascii() || body.len() != total {
+      return Err(ProofError::InvalidEncoding);
+    }
+    let mut signatures = vec![];
+    let mut offset = 0;
+    for size in ring_sizes {
+      let end = offset + size * ENCODED_SIGNATURE_SIZE;
+      let bytes = Base58::decode(&body[offset..end]).ok_or(ProofError::InvalidEncoding)?;
+      signatures.push(RingSignature::from_bytes(&bytes, *size).map_err(ProofError::Ring)?);
+      offset = end;
+    }
+    Ok(SpendProof { signatures })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn key_pair() -> ([u8; 32], [u8; 32]) {
+    let mut public_key: [u8; 32] = [0; 32];
+    let mut secret_key: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut public_key, &mut secret_key);
+    (public_key, secret_key)
+  }
+
+  #[test]
+  fn should_prove_outbound_payment() {
+    let txid = Hash::fast(b"transaction");
+    let (view_public, view_secret) = key_pair();
+    let (spend_public, _) = key_pair();
+    let (tx_public, tx_secret) = key_pair();
+    let (extra_public, extra_secret) = key_pair();
+    let tx_keys = [tx_secret, extra_secret];
+    let tx_public_keys = [tx_public, extra_public];
+
+    let proof = TxProof::generate_outbound(&txid, b"invoice 42", &tx_keys, &view_public, None).unwrap();
+    assert!(proof.verify(&txid, b"invoice 42", &tx_public_keys, &view_public, None));
+    assert!(!proof.verify(&txid, b"invoice 43", &tx_public_keys, &view_public, None));
+    assert!(!proof.verify(&[0; 32], b"invoice 42", &tx_public_keys, &view_public, None));
+    assert!(!proof.verify(&txid, b"invoice 42", &tx_public_keys[..1], &view_public, None));
+    assert!(!proof.verify(&txid, b"invoice 42", &tx_public_keys, &spend_public, None));
+    assert!(proof.derivations()[0] == Key::generate_key_derivation(&tx_public, &view_secret));
+
+    let encoded = proof.encode();
+    assert!(encoded.starts_with("OutProofV2") && encoded.len() == 10 + 2 * 132);
+    assert!(TxProof::decode(&encoded) == Ok(proof));
+
+    // subaddress: R = r * B
+    let r = Scalar::from(tx_secret);
+    let subaddress_tx_public = (r * Point::from_bytes(&spend_public).unwrap()).to_bytes();
+    let proof = TxProof::generate_outbound(&txid, b"", &[tx_secret], &view_public, Some(&spend_public)).unwrap();
+    assert!(proof.verify(&txid, b"", &[subaddress_tx_public], &view_public, Some(&spend_public)));
+    assert!(!proof.verify(&txid, b"", &[subaddress_tx_public], &view_public, None));
+  }
+
+  #[test]
+  fn should_prove_inbound_payment() {
+    let txid = Hash::fast(b"transaction");
+    let (view_public, view_secret) = key_pair();
+    let (tx_public, tx_secret) = key_pair();
+
+    let proof = TxProof::generate_inbound(&txid, b"", &[tx_public], &view_secret).unwrap();
+    assert!(proof.verify(&txid, b"", &[tx_public], &view_public, None));
+    assert!(!proof.verify(&txid, b"x", &[tx_public], &view_public, None));
+    assert!(proof.derivations()[0] == Key::generate_key_derivation(&view_public, &tx_secret));
+
+    let encoded = proof.encode();
+    assert!(encoded.starts_with("InProofV2"));
+    let decoded = TxProof::decode(&encoded).unwrap();
+    assert!(decoded.verify(&txid, b"", &[tx_public], &view_public, None));
+
+    // an inbound proof is not an outbound one
+    let mut outbound = decoded.clone();
+    outbound.outbound = true;
+    assert!(!outbound.verify(&txid, b"", &[tx_public], &view_public, None));
+  }
+
+  #[test]
+  fn should_reject_malformed_proof_strings() {
+    assert!(TxProof::decode("OutProofV1") == Err(ProofError::InvalidHeader));
+    assert!(TxProof::decode("OutProofV2") == Err(ProofError::InvalidEncoding));
+    let (view_public, _) = key_pair();
+    let (_, tx_secret) = key_pair();
+    let encoded = TxProof::generate_outbound(&[1; 32], b"", &[tx_secret], &view_public, None)
+      .unwrap()
+      .encode();
+    assert!(TxProof::decode(&encoded[..encoded.len() - 1]) == Err(ProofError::InvalidEncoding));
+    // '0' is not in the Base58 alphabet
+    let mut bytes = encoded.into_bytes();
+    bytes[10] = b'0';
+    let corrupted = String::from_utf8(bytes).unwrap();
+    assert!(TxProof::decode(&corrupted) == Err(ProofError::InvalidEncoding));
+  }
+
+  // An output of amount paid to (view_public, spend_public) at index 0 of a
+  // new transaction, with what the owner and the chain know about it.
+  fn receive(view_secret: &[u8; 32], spend_public: &[u8; 32], spend_secret: &[u8; 32], amount: u64) -> (OwnedOutput, ReserveOutput) {
+    let (tx_public, _) = key_pair();
+    let derivation = Key::generate_key_derivation(&tx_public, view_secret);
+    let shared_secret = Key::derive_secret_key(&derivation, 0, &[0; 32]);
+    let owned = OwnedOutput {
+      public_key: Key::derive_public_key(&derivation, 0, spend_public),
+      secret_key: Key::derive_secret_key(&derivation, 0, spend_secret),
+      shared_secret,
+    };
+    let output = ReserveOutput {
+      public_key: owned.public_key,
+      commitment: Commitment::commit(&Commitment::mask_from_secret(&shared_secret), amount),
+      encrypted_amount: Commitment::encrypt_amount(amount, &shared_secret),
+    };
+    (owned, output)
+  }
+
+  #[test]
+  fn should_prove_reserve() {
+    let (_, view_secret) = key_pair();
+    let (spend_public, spend_secret) = key_pair();
+    let (first, first_output) = receive(&view_secret, &spend_public, &spend_secret, 1_000);
+    let (second, second_output) = receive(&view_secret, &spend_public, &spend_secret, 234);
+    let key_images = vec![
+      Key::generate_key_image(&first.public_key, &first.secret_key),
+      Key::generate_key_image(&second.public_key, &second.secret_key),
+    ];
+    let outputs = vec![first_output, second_output];
+
+    let proof = ReserveProof::generate(b"reserves", &[first, second], &spend_public, &spend_secret).unwrap();
+    assert!(proof.verify(b"reserves", &spend_public, &outputs) == Some(1_234));
+    assert!(proof.key_images() == key_images);
+    assert!(proof.verify(b"reserve", &spend_public, &outputs).is_none());
+    assert!(proof.verify(b"reserves", &spend_public, &outputs[..1]).is_none());
+    let (other_public, _) = key_pair();
+    assert!(proof.verify(b"reserves", &other_public, &outputs).is_none());
+
+    // a wrong key image, or one listed twice, is rejected
+    let mut forged = proof.clone();
+    forged.entries[1].key_image = forged.entries[0].key_image;
+    assert!(forged.verify(b"reserves", &spend_public, &outputs).is_none());
+
+    // the shared secret must open the commitment
+    let mut forged = proof.clone();
+    forged.entries[0].shared_secret = [1; 32];
+    assert!(forged.verify(b"reserves", &spend_public, &outputs).is_none());
+  }
+
+  #[test]
+  fn should_not_prove_reserve_of_other_keys() {
+    let (_, view_secret) = key_pair();
+    let (spend_public, spend_secret) = key_pair();
+    let (other_public, other_secret) = key_pair();
+    let (owned, _) = receive(&view_secret, &spend_public, &spend_secret, 1);
+    assert!(ReserveProof::generate(b"", &[owned], &other_public, &spend_secret) == Err(ProofError::SecretKeyMismatch));
+
+    // an entry copied from someone else's proof does not verify in a proof
+    // of another address, even signed again by that address
+    let (victim, victim_output) = receive(&view_secret, &spend_public, &spend_secret, 1_000);
+    let published = ReserveProof::generate(b"reserves", &[victim], &spend_public, &spend_secret).unwrap();
+    let (_, thief_view_secret) = key_pair();
+    let (thief, thief_output) = receive(&thief_view_secret, &other_public, &other_secret, 1);
+    let mut stolen = ReserveProof::generate(b"reserves", &[thief], &other_public, &other_secret).unwrap();
+    stolen.entries.push(published.entries[0].clone());
+    let outputs = vec![thief_output, victim_output];
+    let parts: Vec<([u8; 32], [u8; 32], [u8; 32])> = outputs
+      .iter()
+      .zip(stolen.entries.iter())
+      .map(|(output, entry)| (output.public_key, entry.key_image, entry.shared_secret))
+      .collect();
+    let prefix_hash = ReserveProof::prefix_hash(b"reserves", &other_public, &parts);
+    stolen.signature = Key::generate_signature(&prefix_hash, &other_public, &other_secret);
+    assert!(stolen.verify(b"reserves", &other_public, &outputs).is_none());
+    let (mut owned, _) = receive(&view_secret, &spend_public, &spend_secret, 1);
+    owned.secret_key = other_secret;
+    assert!(ReserveProof::generate(b"", &[owned], &spend_public, &spend_secret) == Err(ProofError::SecretKeyMismatch));
+  }
+
+  #[test]
+  fn should_prove_spend() {
+    let txid = Hash::fast(b"spending transaction");
+    let mut inputs = vec![];
+    for (size, real_index) in &[(3, 1), (1, 0)] {
+      let pubs: Vec<([u8; 32], [u8; 32])> = (0..*size).map(|_| key_pair()).collect();
+      let (public_key, secret_key) = pubs[*real_index];
+      inputs.push(SpendInput {
+        image: Key::generate_key_image(&public_key, &secret_key),
+        pubs: pubs.iter().map(|(p, _)| *p).collect(),
+        secret_key,
+        real_index: *real_index,
+      });
+    }
+    let rings: Vec<([u8; 32], Vec<[u8; 32]>)> = inputs.iter().map(|input| (input.image, input.pubs.clone())).collect();
+
+    let proof = SpendProof::generate(&txid, b"paid", &inputs).unwrap();
+    assert!(proof.verify(&txid, b"paid", &rings));
+    assert!(!proof.verify(&txid, b"not paid", &rings));
+    assert!(!proof.verify(&[0; 32], b"paid", &rings));
+    assert!(!proof.verify(&txid, b"paid", &rings[..1]));
+    let swapped = vec![(rings[1].0, rings[0].1.clone()), (rings[0].0, rings[1].1.clone())];
+    assert!(!proof.verify(&txid, b"paid", &swapped));
+
+    let proof = SpendProof::generate_with_rng(&txid, b"paid", &inputs, &mut rand::thread_rng()).unwrap();
+    let encoded = proof.encode();
+    assert!(encoded.starts_with("SpendProofV1") && encoded.len() == 12 + 4 * 88);
+    let decoded = SpendProof::decode(&encoded, &[3, 1]).unwrap();
+    assert!(decoded == proof && decoded.verify(&txid, b"paid", &rings));
+    assert!(SpendProof::decode(&encoded, &[3]) == Err(ProofError::InvalidEncoding));
+    assert!(SpendProof::decode("SpendProofV2", &[3]) == Err(ProofError::InvalidHeader));
+
+    // someone who does not hold a real key cannot prove the spend
+    let mut stranger = inputs.remove(0);
+    stranger.secret_key = key_pair().1;
+    assert!(SpendProof::generate(&txid, b"paid", &[stranger]) == Err(ProofError::Ring(RingError::SecretKeyMismatch)));
+  }
+}
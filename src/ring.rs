@@ -1,97 +1,747 @@
-extern "C" {
-  fn vec_resize(size: usize);
-  fn vec_clear();
-  fn vec_push(i: usize, key: *const u8);
-  fn vec_data() -> *const *const u8;
-
-  fn sig_resize(size: usize);
-  fn sig_clear();
-  fn sig_push(i: usize, key: *const u8);
-  fn sig_data() -> *const u8;
-  fn generate_ring_signature(
-    prefix_hash: *const u8,
-    image: *const u8,
-    pubs: *const *const u8,
-    pubs_count: usize,
-    sec: *const u8,
-    sec_index: usize,
-    sig: *const u8,
-  );
-
-  fn check_ring_signature(
-    prefix_hash: *const u8,
-    image: *const u8,
-    pubs: *const *const u8,
-    pubs_count: usize,
-    sig: *const u8,
-  ) -> bool;
+use super::key::{Key, KeyImage};
+use rand::seq::SliceRandom;
+use rand_core::{CryptoRng, RngCore};
+use super::scalar::{EllipticCurveScalar, Point, Scalar, ThreadRandom};
+
+#[derive(Debug, PartialEq)]
+pub enum RingError {
+  EmptyRing,
+  InvalidIndex,
+  InvalidLength,
+  InvalidPublicKey,
+  InvalidScalar,
+  KeyImageMismatch,
+  SecretKeyMismatch,
+}
+
+// One (c, r) pair per ring member, serialised as c || r for each member in
+// ring order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RingSignature {
+  pub pairs: Vec<([u8; 32], [u8; 32])>,
+}
+
+impl RingSignature {
+  fn from_chunks(bytes: &[u8]) -> RingSignature {
+    let pairs = bytes
+      .chunks(64)
+      .map(|chunk| {
+        let mut c: [u8; 32] = [0; 32];
+        let mut r: [u8; 32] = [0; 32];
+        c.copy_from_slice(&chunk[..32]);
+        r.copy_from_slice(&chunk[32..]);
+        (c, r)
+      })
+      .collect();
+    RingSignature { pairs }
+  }
+
+  pub fn from_bytes(bytes: &[u8], pubs_count: usize) -> Result<RingSignature, RingError> {
+    if pubs_count == 0 || bytes.len() != 64 * pubs_count {
+      return Err(RingError::InvalidLength);
+    }
+    let signature = RingSignature::from_chunks(bytes);
+    if !signature.has_valid_scalars() {
+      return Err(RingError::InvalidScalar);
+    }
+    Ok(signature)
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(64 * self.pairs.len());
+    for (c, r) in &self.pairs {
+      bytes.extend_from_slice(c);
+      bytes.extend_from_slice(r);
+    }
+    bytes
+  }
+
+  pub fn len(&self) -> usize {
+    self.pairs.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.pairs.is_empty()
+  }
+
+  // Every c and r must be a reduced scalar, otherwise the same signature
+  // has several encodings.
+  pub fn has_valid_scalars(&self) -> bool {
+    self
+      .pairs
+      .iter()
+      .all(|(c, r)| EllipticCurveScalar::check(c) && EllipticCurveScalar::check(r))
+  }
 }
 
 pub struct Ring {}
 
 impl Ring {
+  fn check_signer(image: &[u8; 32], pubs: &[[u8; 32]], sec: &[u8; 32], sec_index: usize) -> Result<(), RingError> {
+    if pubs.is_empty() {
+      return Err(RingError::EmptyRing);
+    }
+    if sec_index >= pubs.len() {
+      return Err(RingError::InvalidIndex);
+    }
+    let mut public_key: [u8; 32] = [0; 32];
+    if !Key::secret_to_public(sec, &mut public_key) || public_key != pubs[sec_index] {
+      return Err(RingError::SecretKeyMismatch);
+    }
+    if Key::generate_key_image(&pubs[sec_index], sec) != *image {
+      return Err(RingError::KeyImageMismatch);
+    }
+    if !pubs.iter().all(Key::check_public_key) {
+      return Err(RingError::InvalidPublicKey);
+    }
+    Ok(())
+  }
+
+  // The secret key must be the one of pubs[sec_index] and the image its key
+  // image, otherwise the signature would not verify.
   pub fn generate_signature(
     prefix_hash: &[u8; 32],
     image: &[u8; 32],
-    pubs: &Vec<[u8; 32]>,
-    pubs_count: usize,
+    pubs: &[[u8; 32]],
+    sec: &[u8; 32],
+    sec_index: usize,
+  ) -> Result<RingSignature, RingError> {
+    Ring::generate_signature_with_rng(prefix_hash, image, pubs, sec, sec_index, &mut ThreadRandom)
+  }
+
+  // generate_ring_signature in Rust, nonce(0) being the real nonce and
+  // nonce(2i + 1), nonce(2i + 2) the (c, r) pair of decoy i, drawn in the
+  // order of the C code so that its random stream gives its signature.
+  fn generate_signature_with_nonces<F: FnMut(u64) -> Scalar>(
+    prefix_hash: &[u8; 32],
+    image: &[u8; 32],
+    pubs: &[[u8; 32]],
+    sec: &[u8; 32],
+    sec_index: usize,
+    mut nonce: F,
+  ) -> Result<RingSignature, RingError> {
+    Ring::check_signer(image, pubs, sec, sec_index)?;
+    let image_point = Point::from_bytes(image).ok_or(RingError::KeyImageMismatch)?;
+
+    let mut pairs: Vec<(Scalar, Scalar)> = vec![(Scalar::zero(), Scalar::zero()); pubs.len()];
+    let mut buf = prefix_hash.to_vec();
+    let mut sum = Scalar::zero();
+    let mut k = Scalar::zero();
+    for (i, public_key) in pubs.iter().enumerate() {
+      let hashed = Point::hash_to_point(public_key);
+      let (a, b) = if i == sec_index {
+        k = nonce(0);
+        (Point::mul_base(&k), k * hashed)
+      } else {
+        let c = nonce(2 * i as u64 + 1);
+        let r = nonce(2 * i as u64 + 2);
+        let point = Point::from_bytes(public_key).ok_or(RingError::InvalidPublicKey)?;
+        pairs[i] = (c, r);
+        sum = sum + c;
+        (Point::double_mul_base(&c, &point, &r), r * hashed + c * image_point)
+      };
+      buf.extend_from_slice(&a.to_bytes());
+      buf.extend_from_slice(&b.to_bytes());
+    }
+    let c = Scalar::hash(&buf) - sum;
+    pairs[sec_index] = (c, c.mul_sub(&Scalar::from(*sec), &k));
+    Ok(RingSignature {
+      pairs: pairs.iter().map(|(c, r)| (c.to_bytes(), r.to_bytes())).collect(),
+    })
+  }
+
+  pub fn generate_signature_with_rng<R: RngCore + CryptoRng>(
+    prefix_hash: &[u8; 32],
+    image: &[u8; 32],
+    pubs: &[[u8; 32]],
+    sec: &[u8; 32],
+    sec_index: usize,
+    rng: &mut R,
+  ) -> Result<RingSignature, RingError> {
+    Ring::generate_signature_with_nonces(prefix_hash, image, pubs, sec, sec_index, |_| {
+      Scalar::random_with_rng(rng)
+    })
+  }
+
+  // The signature generate_signature makes, with the real nonce and the
+  // decoy (c, r) pairs derived from the secret key, message and ring rather
+  // than drawn from the global RNG.
+  pub fn generate_deterministic_signature(
+    prefix_hash: &[u8; 32],
+    image: &[u8; 32],
+    pubs: &[[u8; 32]],
+    sec: &[u8; 32],
+    sec_index: usize,
+  ) -> Result<RingSignature, RingError> {
+    let mut transcript = prefix_hash.to_vec();
+    transcript.extend_from_slice(image);
+    for public_key in pubs {
+      transcript.extend_from_slice(public_key);
+    }
+    Ring::generate_signature_with_nonces(prefix_hash, image, pubs, sec, sec_index, |i| {
+      Key::deterministic_nonce(sec, &transcript, i)
+    })
+  }
+
+  pub fn check_signature(prefix_hash: &[u8; 32], image: &[u8; 32], pubs: &[[u8; 32]], signature: &RingSignature) -> bool {
+    if pubs.is_empty() || signature.len() != pubs.len() {
+      return false;
+    }
+    if !KeyImage::is_valid(image) || !signature.has_valid_scalars() {
+      return false;
+    }
+    let image_point = match Point::from_bytes(image) {
+      Some(point) => point,
+      None => return false,
+    };
+    // check_ring_signature in Rust: sum(c) == H(prefix || L || R) with
+    // L = c * P + r * G and R = r * Hp(P) + c * I for every member
+    let mut buf = prefix_hash.to_vec();
+    let mut sum = Scalar::zero();
+    for (public_key, (c, r)) in pubs.iter().zip(signature.pairs.iter()) {
+      let point = match Point::from_bytes(public_key) {
+        Some(point) => point,
+        None => return false,
+      };
+      let (c, r) = (Scalar::from(*c), Scalar::from(*r));
+      let a = Point::double_mul_base(&c, &point, &r);
+      let b = r * Point::hash_to_point(public_key) + c * image_point;
+      buf.extend_from_slice(&a.to_bytes());
+      buf.extend_from_slice(&b.to_bytes());
+      sum = sum + c;
+    }
+    (Scalar::hash(&buf) - sum).is_zero()
+  }
+}
+
+// The real output is hidden among the decoys at a random position which the
+// caller gets back alongside the ordered ring.
+pub struct RingBuilder {
+  public_key: [u8; 32],
+  secret_key: [u8; 32],
+  decoys: Vec<[u8; 32]>,
+}
+
+pub struct SignedRing {
+  pub pubs: Vec<[u8; 32]>,
+  pub sec_index: usize,
+  pub image: [u8; 32],
+  pub signature: RingSignature,
+}
+
+impl RingBuilder {
+  pub fn new(public_key: &[u8; 32], secret_key: &[u8; 32]) -> RingBuilder {
+    RingBuilder {
+      public_key: *public_key,
+      secret_key: *secret_key,
+      decoys: vec![],
+    }
+  }
+
+  pub fn decoy(mut self, public_key: &[u8; 32]) -> RingBuilder {
+    self.decoys.push(*public_key);
+    self
+  }
+
+  pub fn decoys(mut self, public_keys: &[[u8; 32]]) -> RingBuilder {
+    self.decoys.extend_from_slice(public_keys);
+    self
+  }
+
+  // None when a key appears twice in the ring, which would both reveal the
+  // real output and make the signature fail to verify, or when the secret
+  // key does not belong to the real output.
+  pub fn sign(&self, prefix_hash: &[u8; 32]) -> Option<SignedRing> {
+    self.sign_with_rng(prefix_hash, &mut rand::thread_rng())
+  }
+
+  pub fn sign_with_rng<R: RngCore + CryptoRng>(&self, prefix_hash: &[u8; 32], rng: &mut R) -> Option<SignedRing> {
+    let mut pubs = self.decoys.clone();
+    pubs.push(self.public_key);
+    let mut sorted = pubs.clone();
+    sorted.sort();
+    sorted.dedup();
+    if sorted.len() != pubs.len() {
+      return None;
+    }
+    pubs.shuffle(rng);
+    let sec_index = pubs.iter().position(|key| *key == self.public_key)?;
+    let image = Key::generate_key_image(&self.public_key, &self.secret_key);
+    let signature = Ring::generate_signature_with_rng(prefix_hash, &image, &pubs, &self.secret_key, sec_index, rng).ok()?;
+    Some(SignedRing {
+      pubs,
+      sec_index,
+      image,
+      signature,
+    })
+  }
+}
+
+// Domain separators, zero padded to 32 bytes as in the reference CLSAG.
+const CLSAG_ROUND: &[u8] = b"CLSAG_round";
+const CLSAG_AGG_0: &[u8] = b"CLSAG_agg_0";
+const CLSAG_AGG_1: &[u8] = b"CLSAG_agg_1";
+
+// Concise linkable ring signature over pairs of one-time keys and amount
+// commitments. d is the commitment key image z * Hp(P) multiplied by 1/8.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClsagSignature {
+  pub s: Vec<[u8; 32]>,
+  pub c1: [u8; 32],
+  pub d: [u8; 32],
+}
+
+pub struct Clsag {}
+
+struct ClsagRing {
+  keys: Vec<Point>,
+  commitments: Vec<Point>,
+  hashed: Vec<Point>,
+  mu_p: Scalar,
+  mu_c: Scalar,
+  round: Vec<u8>,
+}
+
+fn domain(tag: &[u8]) -> Vec<u8> {
+  let mut data = tag.to_vec();
+  data.resize(32, 0);
+  data
+}
+
+fn to_scalar(data: &[u8]) -> Scalar {
+  Scalar::from(EllipticCurveScalar::to_hash(data))
+}
+
+impl Clsag {
+  // Unpacks the ring and computes the aggregation coefficients and the
+  // common prefix of every round hash.
+  fn prepare(
+    message: &[u8; 32],
+    pubs: &[[u8; 32]],
+    commitments: &[[u8; 32]],
+    pseudo_output: &[u8; 32],
+    image: &[u8; 32],
+    d: &[u8; 32],
+  ) -> Option<ClsagRing> {
+    if pubs.is_empty() || pubs.len() != commitments.len() {
+      return None;
+    }
+    let pseudo = Point::from_bytes(pseudo_output)?;
+    let mut keys = Vec::with_capacity(pubs.len());
+    let mut offsets = Vec::with_capacity(pubs.len());
+    let mut hashed = Vec::with_capacity(pubs.len());
+    let mut ring: Vec<u8> = vec![];
+    for key in pubs {
+      keys.push(Point::from_bytes(key)?);
+      hashed.push(Point::hash_to_point(key));
+      ring.extend_from_slice(key);
+    }
+    for commitment in commitments {
+      offsets.push(Point::from_bytes(commitment)? - pseudo);
+      ring.extend_from_slice(commitment);
+    }
+
+    let mut agg = ring.clone();
+    agg.extend_from_slice(image);
+    agg.extend_from_slice(d);
+    agg.extend_from_slice(pseudo_output);
+    let mu_p = to_scalar(&[domain(CLSAG_AGG_0), agg.clone()].concat());
+    let mu_c = to_scalar(&[domain(CLSAG_AGG_1), agg].concat());
+
+    let mut round = domain(CLSAG_ROUND);
+    round.extend_from_slice(&ring);
+    round.extend_from_slice(pseudo_output);
+    round.extend_from_slice(message);
+    Some(ClsagRing {
+      keys,
+      commitments: offsets,
+      hashed,
+      mu_p,
+      mu_c,
+      round,
+    })
+  }
+
+  fn challenge(ring: &ClsagRing, l: &Point, r: &Point) -> Scalar {
+    let mut data = ring.round.clone();
+    data.extend_from_slice(&l.to_bytes());
+    data.extend_from_slice(&r.to_bytes());
+    to_scalar(&data)
+  }
+
+  // L = s * G + c * (mu_p * P + mu_c * C), R = s * Hp(P) + c * (mu_p * I + mu_c * D)
+  fn round(ring: &ClsagRing, i: usize, s: &Scalar, c: &Scalar, image: &Point, d: &Point) -> Scalar {
+    let c_p = ring.mu_p * c;
+    let c_c = ring.mu_c * c;
+    let l = Point::mul_base(s) + c_p * ring.keys[i] + c_c * ring.commitments[i];
+    let r = s * ring.hashed[i] + c_p * image + c_c * d;
+    Clsag::challenge(ring, &l, &r)
+  }
+
+  // Signs with the one-time secret key of pubs[sec_index] and the difference
+  // between its commitment mask and the pseudo output mask. The key image is
+  // the usual Key::generate_key_image of that one-time key.
+  #[allow(clippy::too_many_arguments)]
+  pub fn generate_signature(
+    message: &[u8; 32],
+    image: &[u8; 32],
+    pubs: &[[u8; 32]],
+    commitments: &[[u8; 32]],
+    pseudo_output: &[u8; 32],
+    sec: &[u8; 32],
+    mask_difference: &[u8; 32],
+    sec_index: usize,
+  ) -> Option<ClsagSignature> {
+    Clsag::generate_signature_with_rng(
+      message,
+      image,
+      pubs,
+      commitments,
+      pseudo_output,
+      sec,
+      mask_difference,
+      sec_index,
+      &mut rand::thread_rng(),
+    )
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  pub fn generate_signature_with_rng<R: RngCore + CryptoRng>(
+    message: &[u8; 32],
+    image: &[u8; 32],
+    pubs: &[[u8; 32]],
+    commitments: &[[u8; 32]],
+    pseudo_output: &[u8; 32],
     sec: &[u8; 32],
+    mask_difference: &[u8; 32],
     sec_index: usize,
-  ) -> Vec<u8> {
-    let mut signature: Vec<u8> = vec![0; pubs_count * 64];
-    unsafe {
-      vec_clear();
-      vec_resize(pubs.len());
-      for i in 0..pubs.len() {
-        vec_push(i, pubs[i].as_ptr());
+    rng: &mut R,
+  ) -> Option<ClsagSignature> {
+    if sec_index >= pubs.len() {
+      return None;
+    }
+    let p = Scalar::from(*sec);
+    let z = Scalar::from(*mask_difference);
+    let hashed = Point::hash_to_point(&pubs[sec_index]);
+    let image_point = p * hashed;
+    if image_point.to_bytes() != *image {
+      return None;
+    }
+    let d_point = z * hashed;
+    let inv8 = Scalar::from(8u64).invert()?;
+    let d = (inv8 * d_point).to_bytes();
+
+    let ring = Clsag::prepare(message, pubs, commitments, pseudo_output, image, &d)?;
+    if Point::mul_base(&p) != ring.keys[sec_index] || Point::mul_base(&z) != ring.commitments[sec_index] {
+      return None;
+    }
+
+    let n = pubs.len();
+    let alpha = Scalar::random_with_rng(rng);
+    let mut c = Clsag::challenge(&ring, &Point::mul_base(&alpha), &(alpha * hashed));
+    let mut s = vec![Scalar::zero(); n];
+    let mut c1 = c;
+    let mut i = (sec_index + 1) % n;
+    if i == 0 {
+      c1 = c;
+    }
+    while i != sec_index {
+      s[i] = Scalar::random_with_rng(rng);
+      c = Clsag::round(&ring, i, &s[i], &c, &image_point, &d_point);
+      i = (i + 1) % n;
+      if i == 0 {
+        c1 = c;
       }
-      generate_ring_signature(
-        prefix_hash.as_ptr(),
-        image.as_ptr(),
-        vec_data(),
-        pubs_count,
-        sec.as_ptr(),
-        sec_index,
-        signature.as_mut_ptr(),
-      );
-      vec_clear();
-    }
-    signature
+    }
+    s[sec_index] = c.mul_sub(&(ring.mu_p * p + ring.mu_c * z), &alpha);
+
+    Some(ClsagSignature {
+      s: s.iter().map(|s| s.to_bytes()).collect(),
+      c1: c1.to_bytes(),
+      d,
+    })
   }
+
   pub fn check_signature(
-    prefix_hash: &[u8; 32],
+    message: &[u8; 32],
     image: &[u8; 32],
-    pubs: &Vec<[u8; 32]>,
-    pubs_count: usize,
-    signatures: &Vec<u8>,
+    pubs: &[[u8; 32]],
+    commitments: &[[u8; 32]],
+    pseudo_output: &[u8; 32],
+    signature: &ClsagSignature,
   ) -> bool {
-    let result: bool;
-    unsafe {
-      assert!(signatures.len() == 64 * pubs_count);
-      sig_clear();
-      sig_resize(pubs.len());
-      for i in 0..pubs.len() {
-        let mut sign: [u8; 64] = [0; 64];
-        for j in 0..64 {
-          sign[j] = signatures[i * 64 + j];
-        }
-        sig_push(i, sign.as_ptr());
+    if !KeyImage::is_valid(image) || signature.s.len() != pubs.len() {
+      return false;
+    }
+    let image_point = Point::from_bytes(image).unwrap();
+    if image_point.is_identity() {
+      return false;
+    }
+    let d_point = match Point::from_bytes(&signature.d) {
+      Some(d) => d.mul_by_pow_2(3),
+      None => return false,
+    };
+    let ring = match Clsag::prepare(message, pubs, commitments, pseudo_output, image, &signature.d) {
+      Some(ring) => ring,
+      None => return false,
+    };
+    let c1 = match Scalar::from_canonical_bytes(&signature.c1) {
+      Some(c1) => c1,
+      None => return false,
+    };
+    let mut c = c1;
+    for (i, s) in signature.s.iter().enumerate() {
+      let s = match Scalar::from_canonical_bytes(s) {
+        Some(s) => s,
+        None => return false,
+      };
+      c = Clsag::round(&ring, i, &s, &c, &image_point, &d_point);
+    }
+    c == c1
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::commitment::Commitment;
+  use super::super::key::Key;
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+  use std::fs::{canonicalize, File};
+  use std::io::{prelude::*, BufReader};
+  use std::path::PathBuf;
+
+  fn key_pair() -> ([u8; 32], [u8; 32]) {
+    let mut public_key: [u8; 32] = [0; 32];
+    let mut secret_key: [u8; 32] = [0; 32];
+    Key::generate_key_pair(&mut public_key, &mut secret_key);
+    (public_key, secret_key)
+  }
+
+  fn decode_keys(list: &str) -> Vec<[u8; 32]> {
+    list
+      .split(',')
+      .map(|key| {
+        let mut bytes: [u8; 32] = [0; 32];
+        bytes.copy_from_slice(&hex::decode(key).expect("Error parse key"));
+        bytes
+      })
+      .collect()
+  }
+
+  #[test]
+  fn should_parse_and_serialize_ring_signature() {
+    let prefix_hash = [6; 32];
+    let (public_key, secret_key) = key_pair();
+    let image = Key::generate_key_image(&public_key, &secret_key);
+    let pubs = vec![key_pair().0, public_key, key_pair().0];
+    let signature = Ring::generate_signature(&prefix_hash, &image, &pubs, &secret_key, 1).unwrap();
+    assert!(signature.len() == 3 && signature.has_valid_scalars());
+
+    let bytes = signature.to_bytes();
+    assert!(bytes.len() == 192);
+    let parsed = RingSignature::from_bytes(&bytes, 3).unwrap();
+    assert!(parsed == signature);
+    assert!(Ring::check_signature(&prefix_hash, &image, &pubs, &parsed));
+
+    assert!(RingSignature::from_bytes(&bytes[..128], 3) == Err(RingError::InvalidLength));
+    assert!(RingSignature::from_bytes(&bytes, 2) == Err(RingError::InvalidLength));
+    assert!(RingSignature::from_bytes(&[], 0) == Err(RingError::InvalidLength));
+    assert!(!Ring::check_signature(&prefix_hash, &image, &pubs[..2], &signature));
+
+    // c or r at or above the group order
+    for offset in &[0, 32, 64 + 31] {
+      let mut bad = bytes.clone();
+      bad[*offset] = 0xff;
+      bad[*offset - *offset % 32 + 31] = 0xff;
+      assert!(RingSignature::from_bytes(&bad, 3) == Err(RingError::InvalidScalar));
+    }
+    let mut unreduced = signature.clone();
+    unreduced.pairs[2].1 = [0xff; 32];
+    assert!(!Ring::check_signature(&prefix_hash, &image, &pubs, &unreduced));
+  }
+
+  #[test]
+  fn should_validate_ring_inputs() {
+    let prefix_hash = [2; 32];
+    let (public_key, secret_key) = key_pair();
+    let (other_key, other_secret) = key_pair();
+    let image = Key::generate_key_image(&public_key, &secret_key);
+    let pubs = vec![other_key, public_key];
+    assert!(Ring::generate_signature(&prefix_hash, &image, &[], &secret_key, 0) == Err(RingError::EmptyRing));
+    assert!(Ring::generate_signature(&prefix_hash, &image, &pubs, &secret_key, 2) == Err(RingError::InvalidIndex));
+    assert!(Ring::generate_signature(&prefix_hash, &image, &pubs, &secret_key, 0) == Err(RingError::SecretKeyMismatch));
+    assert!(Ring::generate_signature(&prefix_hash, &image, &pubs, &other_secret, 1) == Err(RingError::SecretKeyMismatch));
+    let other_image = Key::generate_key_image(&other_key, &other_secret);
+    assert!(Ring::generate_signature(&prefix_hash, &other_image, &pubs, &secret_key, 1) == Err(RingError::KeyImageMismatch));
+    let invalid = vec![[0xff; 32], public_key];
+    assert!(Ring::generate_signature(&prefix_hash, &image, &invalid, &secret_key, 1) == Err(RingError::InvalidPublicKey));
+
+    let signature = Ring::generate_signature(&prefix_hash, &image, &pubs, &secret_key, 1).unwrap();
+    assert!(Ring::check_signature(&prefix_hash, &image, &pubs, &signature));
+    assert!(!Ring::check_signature(&prefix_hash, &image, &[], &signature));
+    assert!(!Ring::check_signature(&prefix_hash, &image, &[other_key, public_key, other_key], &signature));
+  }
+
+  #[test]
+  fn should_check_signatures_concurrently() {
+    let threads: Vec<std::thread::JoinHandle<()>> = (1..9)
+      .map(|size| {
+        std::thread::spawn(move || {
+          for _ in 0..20 {
+            let (public_key, secret_key) = key_pair();
+            let image = Key::generate_key_image(&public_key, &secret_key);
+            let mut pubs: Vec<[u8; 32]> = (1..size).map(|_| key_pair().0).collect();
+            pubs.push(public_key);
+            let signature = Ring::generate_signature(&[size as u8; 32], &image, &pubs, &secret_key, size - 1).unwrap();
+            assert!(Ring::check_signature(&[size as u8; 32], &image, &pubs, &signature));
+            assert!(!Ring::check_signature(&[0; 32], &image, &pubs, &signature));
+          }
+        })
+      })
+      .collect();
+    for thread in threads {
+      thread.join().unwrap();
+    }
+  }
+
+  #[test]
+  fn should_generate_deterministic_ring_signature() {
+    let prefix_hash = [4; 32];
+    let (public_key, secret_key) = key_pair();
+    let image = Key::generate_key_image(&public_key, &secret_key);
+    let pubs = vec![key_pair().0, key_pair().0, public_key, key_pair().0];
+    let signature = Ring::generate_deterministic_signature(&prefix_hash, &image, &pubs, &secret_key, 2).unwrap();
+    assert!(Ring::check_signature(&prefix_hash, &image, &pubs, &signature));
+    assert!(signature == Ring::generate_deterministic_signature(&prefix_hash, &image, &pubs, &secret_key, 2).unwrap());
+    let other = Ring::generate_deterministic_signature(&[5; 32], &image, &pubs, &secret_key, 2).unwrap();
+    assert!(Ring::check_signature(&[5; 32], &image, &pubs, &other));
+    assert!(other.pairs.iter().zip(signature.pairs.iter()).all(|(a, b)| a != b));
+    assert!(
+      Ring::generate_deterministic_signature(&prefix_hash, &image, &pubs, &secret_key, 1)
+        == Err(RingError::SecretKeyMismatch)
+    );
+    let single = Ring::generate_deterministic_signature(&prefix_hash, &image, &[public_key], &secret_key, 0).unwrap();
+    assert!(Ring::check_signature(&prefix_hash, &image, &[public_key], &single));
+  }
+
+  #[test]
+  fn should_sign_rings_with_injected_rng() {
+    let prefix_hash = [8; 32];
+    let (public_key, secret_key) = key_pair();
+    let image = Key::generate_key_image(&public_key, &secret_key);
+    let decoys: Vec<[u8; 32]> = (0..6).map(|_| key_pair().0).collect();
+    let mut pubs = decoys.clone();
+    pubs.insert(3, public_key);
+
+    let signature =
+      Ring::generate_signature_with_rng(&prefix_hash, &image, &pubs, &secret_key, 3, &mut StdRng::seed_from_u64(9)).unwrap();
+    assert!(Ring::check_signature(&prefix_hash, &image, &pubs, &signature));
+    let again =
+      Ring::generate_signature_with_rng(&prefix_hash, &image, &pubs, &secret_key, 3, &mut StdRng::seed_from_u64(9)).unwrap();
+    assert!(signature == again);
+
+    let builder = RingBuilder::new(&public_key, &secret_key).decoys(&decoys);
+    let first = builder.sign_with_rng(&prefix_hash, &mut StdRng::seed_from_u64(10)).unwrap();
+    let second = builder.sign_with_rng(&prefix_hash, &mut StdRng::seed_from_u64(10)).unwrap();
+    assert!(first.pubs == second.pubs && first.sec_index == second.sec_index && first.signature == second.signature);
+    assert!(Ring::check_signature(&prefix_hash, &first.image, &first.pubs, &first.signature));
+  }
+
+  #[test]
+  fn should_build_shuffled_ring() {
+    let prefix_hash = [5; 32];
+    let (public_key, secret_key) = key_pair();
+    let decoys: Vec<[u8; 32]> = (0..10).map(|_| key_pair().0).collect();
+    let mut positions = vec![];
+    for _ in 0..20 {
+      let signed = RingBuilder::new(&public_key, &secret_key).decoys(&decoys).sign(&prefix_hash).unwrap();
+      assert!(signed.pubs.len() == 11);
+      assert!(signed.pubs[signed.sec_index] == public_key);
+      assert!(signed.image == Key::generate_key_image(&public_key, &secret_key));
+      assert!(Ring::check_signature(&prefix_hash, &signed.image, &signed.pubs, &signed.signature));
+      positions.push(signed.sec_index);
+    }
+    positions.sort();
+    positions.dedup();
+    assert!(positions.len() > 1);
+
+    let single = RingBuilder::new(&public_key, &secret_key).sign(&prefix_hash).unwrap();
+    assert!(single.pubs == vec![public_key] && single.sec_index == 0);
+    assert!(RingBuilder::new(&public_key, &secret_key).decoy(&decoys[0]).decoy(&decoys[0]).sign(&prefix_hash).is_none());
+    assert!(RingBuilder::new(&public_key, &secret_key).decoy(&public_key).sign(&prefix_hash).is_none());
+  }
+
+  #[test]
+  fn should_check_clsag_known_answers() {
+    let path = PathBuf::from("./tests/clsag.txt");
+    let f = File::open(canonicalize(path).unwrap()).unwrap();
+    let file = BufReader::new(&f);
+    for line in file.lines() {
+      let l = line.unwrap();
+      let split: Vec<&str> = l.split_whitespace().collect();
+      if split[0] != "check_signature" {
+        continue;
       }
-      vec_clear();
-      vec_resize(pubs.len());
-      for i in 0..pubs.len() {
-        vec_push(i, pubs[i].as_ptr());
+      let message = decode_keys(split[1])[0];
+      let image = decode_keys(split[2])[0];
+      let pubs = decode_keys(split[3]);
+      let commitments = decode_keys(split[4]);
+      let pseudo_output = decode_keys(split[5])[0];
+      let bytes = hex::decode(split[6]).expect("Error parse signature");
+      let mut scalars: Vec<[u8; 32]> = bytes
+        .chunks(32)
+        .map(|chunk| {
+          let mut scalar: [u8; 32] = [0; 32];
+          scalar.copy_from_slice(chunk);
+          scalar
+        })
+        .collect();
+      let d = scalars.pop().unwrap();
+      let c1 = scalars.pop().unwrap();
+      let signature = ClsagSignature { s: scalars, c1, d };
+      let expected = split[7] == "true";
+      assert!(Clsag::check_signature(&message, &image, &pubs, &commitments, &pseudo_output, &signature) == expected);
+    }
+  }
+
+  #[test]
+  fn should_sign_with_clsag() {
+    let message = [7; 32];
+    for n in 1..5 {
+      for sec_index in 0..n {
+        let (public_key, secret_key) = key_pair();
+        let image = Key::generate_key_image(&public_key, &secret_key);
+        let mask = Scalar::random();
+        let pseudo_mask = Scalar::random();
+        let mut pubs: Vec<[u8; 32]> = (1..n).map(|_| key_pair().0).collect();
+        let mut commitments: Vec<[u8; 32]> = (1..n)
+          .map(|i| Commitment::commit(&Scalar::random(), i as u64).to_bytes())
+          .collect();
+        pubs.insert(sec_index, public_key);
+        commitments.insert(sec_index, Commitment::commit(&mask, 50).to_bytes());
+        let pseudo_output = Commitment::commit(&pseudo_mask, 50).to_bytes();
+        let z = (mask - pseudo_mask).to_bytes();
+
+        let signature =
+          Clsag::generate_signature(&message, &image, &pubs, &commitments, &pseudo_output, &secret_key, &z, sec_index)
+            .unwrap();
+        assert!(signature.s.len() == n);
+        assert!(Clsag::check_signature(&message, &image, &pubs, &commitments, &pseudo_output, &signature));
+        assert!(!Clsag::check_signature(&[8; 32], &image, &pubs, &commitments, &pseudo_output, &signature));
+
+        let other_image = Key::generate_key_image(&pubs[(sec_index + 1) % n], &secret_key);
+        assert!(n == 1 || !Clsag::check_signature(&message, &other_image, &pubs, &commitments, &pseudo_output, &signature));
+
+        // the pseudo output must commit to the same amount
+        let wrong_output = Commitment::commit(&pseudo_mask, 51).to_bytes();
+        assert!(!Clsag::check_signature(&message, &image, &pubs, &commitments, &wrong_output, &signature));
+        assert!(
+          Clsag::generate_signature(&message, &image, &pubs, &commitments, &wrong_output, &secret_key, &z, sec_index)
+            .is_none()
+        );
       }
-      result = check_ring_signature(
-        prefix_hash.as_ptr(),
-        image.as_ptr(),
-        vec_data(),
-        pubs_count,
-        sig_data(),
-      );
-      vec_clear();
-      sig_clear();
-    }
-    result
+    }
   }
 }
@@ -1,25 +1,132 @@
+use super::consts::{CURVE_ORDER, IDENTITY_POINT};
+use rand_core::{CryptoRng, RngCore};
+use std::ops::{Add, Mul, Neg, Sub};
+
+type FieldElement = [i32; 10];
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct GeP2 {
+  x: FieldElement,
+  y: FieldElement,
+  z: FieldElement,
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct GeP3 {
+  x: FieldElement,
+  y: FieldElement,
+  z: FieldElement,
+  t: FieldElement,
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct GeP1P1 {
+  x: FieldElement,
+  y: FieldElement,
+  z: FieldElement,
+  t: FieldElement,
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+pub(crate) struct GeCached {
+  y_plus_x: FieldElement,
+  y_minus_x: FieldElement,
+  z: FieldElement,
+  t2d: FieldElement,
+}
+
 extern "C" {
+  fn generate_random_bytes_thread_safe(n: usize, result: *mut u8);
   fn check_scalar(scalar: *const u8) -> bool;
-  fn random_scalar(secret_key: *mut u8);
   fn hash_to_scalar(data: *const u8, length: usize, hash: *mut u8);
   fn hash_to_point(hash: *const u8, point: *mut u8);
   fn hash_to_ec_ex(hash: *const u8, ec: *mut u8);
+  fn hash_data_to_ec(data: *const u8, length: usize, point: *mut u8);
+
+  fn sc_reduce(s: *mut u8);
+  fn sc_reduce32(s: *mut u8);
+  fn sc_add(s: *mut u8, a: *const u8, b: *const u8);
+  fn sc_sub(s: *mut u8, a: *const u8, b: *const u8);
+  fn sc_mul(s: *mut u8, a: *const u8, b: *const u8);
+  fn sc_muladd(s: *mut u8, a: *const u8, b: *const u8, c: *const u8);
+  fn sc_mulsub(s: *mut u8, a: *const u8, b: *const u8, c: *const u8);
+
+  fn ge_frombytes_vartime(h: *mut GeP3, s: *const u8) -> i32;
+  fn ge_p3_tobytes(s: *mut u8, h: *const GeP3);
+  fn ge_tobytes(s: *mut u8, h: *const GeP2);
+  fn ge_p3_to_cached(r: *mut GeCached, p: *const GeP3);
+  fn ge_p1p1_to_p3(r: *mut GeP3, p: *const GeP1P1);
+  fn ge_p1p1_to_p2(r: *mut GeP2, p: *const GeP1P1);
+  fn ge_p3_to_p2(r: *mut GeP2, p: *const GeP3);
+  fn ge_p2_dbl(r: *mut GeP1P1, p: *const GeP2);
+  fn ge_add(r: *mut GeP1P1, p: *const GeP3, q: *const GeCached);
+  fn ge_sub(r: *mut GeP1P1, p: *const GeP3, q: *const GeCached);
+  fn ge_scalarmult_base(h: *mut GeP3, a: *const u8);
+  fn ge_scalarmult_p3(r: *mut GeP3, a: *const u8, p: *const GeP3);
+  fn ge_double_scalarmult_base_vartime(r: *mut GeP2, a: *const u8, p: *const GeP3, b: *const u8);
 }
 
 pub struct EllipticCurveScalar {}
 
+// The per thread Keccak generator of random.c as an RNG, so the defaults
+// drawing from it go through the _with_rng functions and are replayed by
+// setup_random like the C code.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadRandom;
+
+impl RngCore for ThreadRandom {
+  fn next_u32(&mut self) -> u32 {
+    rand_core::impls::next_u32_via_fill(self)
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    rand_core::impls::next_u64_via_fill(self)
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    unsafe { generate_random_bytes_thread_safe(dest.len(), dest.as_mut_ptr()) }
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+    self.fill_bytes(dest);
+    Ok(())
+  }
+}
+
+impl CryptoRng for ThreadRandom {}
+
 pub struct EllipticCurvePoint {}
 
+// A scalar reduced modulo the curve order l.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Scalar {
+  data: [u8; 32],
+}
+
+// A decompressed point of the ed25519 curve.
+#[derive(Clone, Copy)]
+pub struct Point {
+  p3: GeP3,
+}
+
 impl EllipticCurveScalar {
   pub fn check(scalar: &[u8; 32]) -> bool {
     unsafe { return check_scalar(scalar[..].as_ptr()) }
   }
 
   pub fn random(secret_key: &mut [u8; 32]) {
-    unsafe {
-      random_scalar(secret_key.as_mut_ptr());
-    }
+    EllipticCurveScalar::random_with_rng(secret_key, &mut ThreadRandom)
+  }
+
+  // random_scalar drawing from the given RNG instead of the C one.
+  pub fn random_with_rng<R: RngCore + CryptoRng>(secret_key: &mut [u8; 32], rng: &mut R) {
+    *secret_key = Scalar::random_with_rng(rng).to_bytes();
   }
+
   pub fn to_hash(scalar: &[u8]) -> [u8; 32] {
     let mut hash: [u8; 32] = [0; 32];
     unsafe { hash_to_scalar(scalar.as_ptr(), scalar.len(), hash.as_mut_ptr()) }
@@ -31,6 +138,28 @@ impl EllipticCurveScalar {
     unsafe { hash_to_ec_ex(hash.as_ptr(), ec.as_mut_ptr()) }
     ec
   }
+
+  pub fn add(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    (Scalar::from(*a) + Scalar::from(*b)).to_bytes()
+  }
+
+  pub fn sub(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    (Scalar::from(*a) - Scalar::from(*b)).to_bytes()
+  }
+
+  pub fn mul(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    (Scalar::from(*a) * Scalar::from(*b)).to_bytes()
+  }
+
+  // a * b + c
+  pub fn mul_add(a: &[u8; 32], b: &[u8; 32], c: &[u8; 32]) -> [u8; 32] {
+    Scalar::from(*a).mul_add(&Scalar::from(*b), &Scalar::from(*c)).to_bytes()
+  }
+
+  // c - a * b
+  pub fn mul_sub(a: &[u8; 32], b: &[u8; 32], c: &[u8; 32]) -> [u8; 32] {
+    Scalar::from(*a).mul_sub(&Scalar::from(*b), &Scalar::from(*c)).to_bytes()
+  }
 }
 
 impl EllipticCurvePoint {
@@ -41,8 +170,386 @@ impl EllipticCurvePoint {
     }
     point
   }
+
+  pub fn check(point: &[u8; 32]) -> bool {
+    Point::from_bytes(point).is_some()
+  }
+
+  pub fn add(a: &[u8; 32], b: &[u8; 32]) -> Option<[u8; 32]> {
+    Some((Point::from_bytes(a)? + Point::from_bytes(b)?).to_bytes())
+  }
+
+  pub fn sub(a: &[u8; 32], b: &[u8; 32]) -> Option<[u8; 32]> {
+    Some((Point::from_bytes(a)? - Point::from_bytes(b)?).to_bytes())
+  }
+
+  // scalar * G
+  pub fn scalar_mult_base(scalar: &[u8; 32]) -> [u8; 32] {
+    Point::mul_base(&Scalar::from(*scalar)).to_bytes()
+  }
+
+  // scalar * point
+  pub fn scalar_mult(scalar: &[u8; 32], point: &[u8; 32]) -> Option<[u8; 32]> {
+    Some(Point::from_bytes(point)?.mul_unreduced(scalar).to_bytes())
+  }
+
+  // a * point + b * G
+  pub fn double_scalar_mult_base(a: &[u8; 32], point: &[u8; 32], b: &[u8; 32]) -> Option<[u8; 32]> {
+    let point = Point::from_bytes(point)?;
+    Some(Point::double_mul_base(&Scalar::from(*a), &point, &Scalar::from(*b)).to_bytes())
+  }
 }
 
+impl Scalar {
+  pub fn zero() -> Scalar {
+    Scalar { data: [0; 32] }
+  }
+
+  pub fn one() -> Scalar {
+    Scalar::from_u64(1)
+  }
+
+  pub fn from_u64(value: u64) -> Scalar {
+    let mut data: [u8; 32] = [0; 32];
+    data[..8].copy_from_slice(&value.to_le_bytes());
+    Scalar { data }
+  }
+
+  // Only accepts canonical encodings, i.e. values below l.
+  pub fn from_canonical_bytes(bytes: &[u8; 32]) -> Option<Scalar> {
+    if EllipticCurveScalar::check(bytes) {
+      Some(Scalar { data: *bytes })
+    } else {
+      None
+    }
+  }
+
+  pub fn from_bytes_mod_order(bytes: &[u8; 32]) -> Scalar {
+    let mut data = *bytes;
+    unsafe { sc_reduce32(data.as_mut_ptr()) }
+    Scalar { data }
+  }
+
+  pub fn random() -> Scalar {
+    Scalar::random_with_rng(&mut rand::thread_rng())
+  }
+
+  // 64 random bytes reduced modulo l, so the result is uniform.
+  pub fn random_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Scalar {
+    let mut wide: [u8; 64] = [0; 64];
+    rng.fill_bytes(&mut wide);
+    unsafe { sc_reduce(wide.as_mut_ptr()) }
+    let mut data: [u8; 32] = [0; 32];
+    data.copy_from_slice(&wide[..32]);
+    Scalar { data }
+  }
+
+  pub fn hash(data: &[u8]) -> Scalar {
+    Scalar {
+      data: EllipticCurveScalar::to_hash(data),
+    }
+  }
+
+  pub fn to_bytes(&self) -> [u8; 32] {
+    self.data
+  }
+
+  pub fn as_bytes(&self) -> &[u8; 32] {
+    &self.data
+  }
+
+  pub fn is_zero(&self) -> bool {
+    self.data == [0; 32]
+  }
+
+  // self^(l - 2) == self^-1 by Fermat, zero has no inverse.
+  pub fn invert(&self) -> Option<Scalar> {
+    if self.is_zero() {
+      return None;
+    }
+    let mut exponent = CURVE_ORDER;
+    exponent[0] -= 2;
+    let mut result = Scalar::one();
+    for i in (0..253).rev() {
+      result = result * result;
+      if (exponent[i / 8] >> (i % 8)) & 1 == 1 {
+        result = result * self;
+      }
+    }
+    Some(result)
+  }
+
+  // self * b + c
+  pub fn mul_add(&self, b: &Scalar, c: &Scalar) -> Scalar {
+    let mut data: [u8; 32] = [0; 32];
+    unsafe { sc_muladd(data.as_mut_ptr(), self.data.as_ptr(), b.data.as_ptr(), c.data.as_ptr()) }
+    Scalar { data }
+  }
+
+  // c - self * b
+  pub fn mul_sub(&self, b: &Scalar, c: &Scalar) -> Scalar {
+    let mut data: [u8; 32] = [0; 32];
+    unsafe { sc_mulsub(data.as_mut_ptr(), self.data.as_ptr(), b.data.as_ptr(), c.data.as_ptr()) }
+    Scalar { data }
+  }
+}
+
+impl From<[u8; 32]> for Scalar {
+  fn from(bytes: [u8; 32]) -> Scalar {
+    Scalar::from_bytes_mod_order(&bytes)
+  }
+}
+
+impl From<u64> for Scalar {
+  fn from(value: u64) -> Scalar {
+    Scalar::from_u64(value)
+  }
+}
+
+impl Add<&Scalar> for &Scalar {
+  type Output = Scalar;
+  fn add(self, other: &Scalar) -> Scalar {
+    let mut data: [u8; 32] = [0; 32];
+    unsafe { sc_add(data.as_mut_ptr(), self.data.as_ptr(), other.data.as_ptr()) }
+    Scalar { data }
+  }
+}
+
+impl Sub<&Scalar> for &Scalar {
+  type Output = Scalar;
+  fn sub(self, other: &Scalar) -> Scalar {
+    let mut data: [u8; 32] = [0; 32];
+    unsafe { sc_sub(data.as_mut_ptr(), self.data.as_ptr(), other.data.as_ptr()) }
+    Scalar { data }
+  }
+}
+
+impl Mul<&Scalar> for &Scalar {
+  type Output = Scalar;
+  fn mul(self, other: &Scalar) -> Scalar {
+    let mut data: [u8; 32] = [0; 32];
+    unsafe { sc_mul(data.as_mut_ptr(), self.data.as_ptr(), other.data.as_ptr()) }
+    Scalar { data }
+  }
+}
+
+impl Neg for &Scalar {
+  type Output = Scalar;
+  fn neg(self) -> Scalar {
+    Scalar::zero() - self
+  }
+}
+
+impl Neg for Scalar {
+  type Output = Scalar;
+  fn neg(self) -> Scalar {
+    -&self
+  }
+}
+
+impl Point {
+  pub fn identity() -> Point {
+    let mut p3 = GeP3::default();
+    p3.y[0] = 1;
+    p3.z[0] = 1;
+    Point { p3 }
+  }
+
+  pub fn generator() -> Point {
+    Point::mul_base(&Scalar::one())
+  }
+
+  pub fn from_bytes(bytes: &[u8; 32]) -> Option<Point> {
+    let mut p3 = GeP3::default();
+    if unsafe { ge_frombytes_vartime(&mut p3, bytes.as_ptr()) } != 0 {
+      return None;
+    }
+    Some(Point { p3 })
+  }
+
+  pub fn to_bytes(&self) -> [u8; 32] {
+    let mut bytes: [u8; 32] = [0; 32];
+    unsafe { ge_p3_tobytes(bytes.as_mut_ptr(), &self.p3) }
+    bytes
+  }
+
+  // Hp(data), the same mapping used for key images.
+  pub fn hash_to_point(data: &[u8]) -> Point {
+    let mut bytes: [u8; 32] = [0; 32];
+    unsafe { hash_data_to_ec(data.as_ptr(), data.len(), bytes.as_mut_ptr()) }
+    Point::from_bytes(&bytes).unwrap()
+  }
+
+  pub fn is_identity(&self) -> bool {
+    self.to_bytes() == IDENTITY_POINT
+  }
+
+  // l * P == 0, the point has no torsion component.
+  pub fn is_torsion_free(&self) -> bool {
+    self.mul_unreduced(&CURVE_ORDER).is_identity()
+  }
+
+  pub fn mul_base(scalar: &Scalar) -> Point {
+    let mut p3 = GeP3::default();
+    unsafe { ge_scalarmult_base(&mut p3, scalar.data.as_ptr()) }
+    Point { p3 }
+  }
+
+  // a * point + b * G, variable time, only for public data.
+  pub fn double_mul_base(a: &Scalar, point: &Point, b: &Scalar) -> Point {
+    let mut p2 = GeP2::default();
+    let mut bytes: [u8; 32] = [0; 32];
+    unsafe {
+      ge_double_scalarmult_base_vartime(&mut p2, a.data.as_ptr(), &point.p3, b.data.as_ptr());
+      ge_tobytes(bytes.as_mut_ptr(), &p2);
+    }
+    Point::from_bytes(&bytes).unwrap()
+  }
+
+  fn mul_unreduced(&self, scalar: &[u8; 32]) -> Point {
+    let mut p3 = GeP3::default();
+    unsafe { ge_scalarmult_p3(&mut p3, scalar.as_ptr(), &self.p3) }
+    Point { p3 }
+  }
+
+  pub fn double(&self) -> Point {
+    self.mul_by_pow_2(1)
+  }
+
+  // 2^k * P
+  pub fn mul_by_pow_2(&self, k: u32) -> Point {
+    if k == 0 {
+      return *self;
+    }
+    let mut p2 = GeP2::default();
+    let mut p1p1 = GeP1P1::default();
+    let mut p3 = GeP3::default();
+    unsafe {
+      ge_p3_to_p2(&mut p2, &self.p3);
+      for _ in 0..k - 1 {
+        ge_p2_dbl(&mut p1p1, &p2);
+        ge_p1p1_to_p2(&mut p2, &p1p1);
+      }
+      ge_p2_dbl(&mut p1p1, &p2);
+      ge_p1p1_to_p3(&mut p3, &p1p1);
+    }
+    Point { p3 }
+  }
+
+  pub(crate) fn cached(&self) -> GeCached {
+    let mut cached = GeCached::default();
+    unsafe { ge_p3_to_cached(&mut cached, &self.p3) }
+    cached
+  }
+
+  pub(crate) fn add_cached(&self, other: &GeCached) -> Point {
+    let mut p1p1 = GeP1P1::default();
+    let mut p3 = GeP3::default();
+    unsafe {
+      ge_add(&mut p1p1, &self.p3, other);
+      ge_p1p1_to_p3(&mut p3, &p1p1);
+    }
+    Point { p3 }
+  }
+
+  pub(crate) fn sub_cached(&self, other: &GeCached) -> Point {
+    let mut p1p1 = GeP1P1::default();
+    let mut p3 = GeP3::default();
+    unsafe {
+      ge_sub(&mut p1p1, &self.p3, other);
+      ge_p1p1_to_p3(&mut p3, &p1p1);
+    }
+    Point { p3 }
+  }
+}
+
+impl PartialEq for Point {
+  fn eq(&self, other: &Point) -> bool {
+    self.to_bytes() == other.to_bytes()
+  }
+}
+
+impl Eq for Point {}
+
+impl std::fmt::Debug for Point {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "Point({})", hex::encode(self.to_bytes()))
+  }
+}
+
+impl Add<&Point> for &Point {
+  type Output = Point;
+  fn add(self, other: &Point) -> Point {
+    self.add_cached(&other.cached())
+  }
+}
+
+impl Sub<&Point> for &Point {
+  type Output = Point;
+  fn sub(self, other: &Point) -> Point {
+    self.sub_cached(&other.cached())
+  }
+}
+
+impl Neg for &Point {
+  type Output = Point;
+  fn neg(self) -> Point {
+    Point::identity() - self
+  }
+}
+
+impl Neg for Point {
+  type Output = Point;
+  fn neg(self) -> Point {
+    -&self
+  }
+}
+
+impl Mul<&Scalar> for &Point {
+  type Output = Point;
+  fn mul(self, scalar: &Scalar) -> Point {
+    self.mul_unreduced(&scalar.data)
+  }
+}
+
+impl Mul<&Point> for &Scalar {
+  type Output = Point;
+  fn mul(self, point: &Point) -> Point {
+    point * self
+  }
+}
+
+macro_rules! forward_binop {
+  ($imp:ident, $method:ident, $lhs:ty, $rhs:ty, $output:ty) => {
+    impl $imp<$rhs> for $lhs {
+      type Output = $output;
+      fn $method(self, other: $rhs) -> $output {
+        $imp::$method(&self, &other)
+      }
+    }
+    impl $imp<&$rhs> for $lhs {
+      type Output = $output;
+      fn $method(self, other: &$rhs) -> $output {
+        $imp::$method(&self, other)
+      }
+    }
+    impl $imp<$rhs> for &$lhs {
+      type Output = $output;
+      fn $method(self, other: $rhs) -> $output {
+        $imp::$method(self, &other)
+      }
+    }
+  };
+}
+
+forward_binop!(Add, add, Scalar, Scalar, Scalar);
+forward_binop!(Sub, sub, Scalar, Scalar, Scalar);
+forward_binop!(Mul, mul, Scalar, Scalar, Scalar);
+forward_binop!(Add, add, Point, Point, Point);
+forward_binop!(Sub, sub, Point, Point, Point);
+forward_binop!(Mul, mul, Point, Scalar, Point);
+forward_binop!(Mul, mul, Scalar, Point, Point);
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -51,7 +558,7 @@ mod tests {
   use std::path::PathBuf;
   extern crate hex;
   use super::super::key::Key;
-  use super::super::ring::Ring;
+  use super::super::ring::{Ring, RingSignature};
 
   extern "C" {
     fn setup_random(value: i32);
@@ -73,6 +580,66 @@ mod tests {
     fixed
   }
 
+  #[test]
+  fn should_generate_random_scalars_concurrently() {
+    let threads: Vec<std::thread::JoinHandle<Vec<[u8; 32]>>> = (0..8)
+      .map(|_| {
+        std::thread::spawn(|| {
+          (0..1000)
+            .flat_map(|_| {
+              let mut scalar: [u8; 32] = [0; 32];
+              EllipticCurveScalar::random(&mut scalar);
+              let mut public_key: [u8; 32] = [0; 32];
+              let mut secret_key: [u8; 32] = [0; 32];
+              Key::generate_key_pair(&mut public_key, &mut secret_key);
+              vec![scalar, secret_key]
+            })
+            .collect()
+        })
+      })
+      .collect();
+    let mut seen = std::collections::HashSet::new();
+    for thread in threads {
+      for scalar in thread.join().unwrap() {
+        assert!(EllipticCurveScalar::check(&scalar));
+        assert!(seen.insert(scalar));
+      }
+    }
+    assert!(seen.len() == 16000);
+  }
+
+  #[test]
+  fn should_replay_random_per_thread() {
+    let replay = || {
+      unsafe {
+        setup_random(7);
+      }
+      let mut scalar: [u8; 32] = [0; 32];
+      EllipticCurveScalar::random(&mut scalar);
+      let mut public_key: [u8; 32] = [0; 32];
+      let mut secret_key: [u8; 32] = [0; 32];
+      Key::generate_key_pair(&mut public_key, &mut secret_key);
+      let signature = Key::generate_signature(&[1; 32], &public_key, &secret_key);
+      let image = Key::generate_key_image(&public_key, &secret_key);
+      let mut decoy: [u8; 32] = [0; 32];
+      Key::generate_key_pair(&mut decoy, &mut [0; 32]);
+      let pubs = [decoy, public_key];
+      let ring_signature = Ring::generate_signature(&[2; 32], &image, &pubs, &secret_key, 1).unwrap();
+      (scalar, secret_key, signature.to_vec(), ring_signature.to_bytes())
+    };
+    let expected = std::thread::spawn(replay).join().unwrap();
+    assert!(expected.0 != expected.1);
+    // other threads drawing meanwhile do not disturb the replayed sequence
+    let noise = std::thread::spawn(|| {
+      for _ in 0..10000 {
+        let mut scalar: [u8; 32] = [0; 32];
+        EllipticCurveScalar::random(&mut scalar);
+      }
+    });
+    assert!(std::thread::spawn(replay).join().unwrap() == expected);
+    noise.join().unwrap();
+  }
+
   #[test]
   fn should_to_hash() {
     let bytes = hex::decode("2ace").expect("Error parse scalar");
@@ -82,6 +649,69 @@ mod tests {
     assert!(hash == expected.as_slice());
   }
 
+  #[test]
+  fn should_do_curve_arithmetic() {
+    let a = EllipticCurveScalar::to_hash(b"a");
+    let b = EllipticCurveScalar::to_hash(b"b");
+    let c = EllipticCurveScalar::to_hash(b"c");
+    let a_g = EllipticCurvePoint::scalar_mult_base(&a);
+    let b_g = EllipticCurvePoint::scalar_mult_base(&b);
+    let c_g = EllipticCurvePoint::scalar_mult_base(&c);
+
+    let sum = EllipticCurveScalar::add(&a, &b);
+    assert!(EllipticCurvePoint::add(&a_g, &b_g).unwrap() == EllipticCurvePoint::scalar_mult_base(&sum));
+    assert!(EllipticCurveScalar::sub(&sum, &b) == a);
+    assert!(EllipticCurvePoint::sub(&a_g, &b_g).unwrap() == EllipticCurvePoint::scalar_mult_base(&EllipticCurveScalar::sub(&a, &b)));
+
+    let product = EllipticCurveScalar::mul(&a, &b);
+    assert!(EllipticCurvePoint::scalar_mult(&a, &b_g).unwrap() == EllipticCurvePoint::scalar_mult_base(&product));
+    assert!(EllipticCurveScalar::mul_add(&a, &b, &c) == EllipticCurveScalar::add(&product, &c));
+    assert!(EllipticCurveScalar::add(&EllipticCurveScalar::mul_sub(&a, &b, &c), &product) == c);
+
+    let expected = EllipticCurvePoint::add(&EllipticCurvePoint::scalar_mult(&a, &c_g).unwrap(), &b_g).unwrap();
+    assert!(EllipticCurvePoint::double_scalar_mult_base(&a, &c_g, &b).unwrap() == expected);
+
+    let mut invalid: [u8; 32] = [0; 32];
+    invalid[0] = 2;
+    assert!(!EllipticCurvePoint::check(&invalid));
+    assert!(EllipticCurvePoint::add(&a_g, &invalid).is_none());
+    assert!(EllipticCurvePoint::scalar_mult(&a, &invalid).is_none());
+  }
+
+  #[test]
+  fn should_use_scalar_and_point_operators() {
+    let a = Scalar::hash(b"a");
+    let b = Scalar::hash(b"b");
+    let g = Point::generator();
+    assert!(g.to_bytes()[0] == 0x58);
+    assert!(Point::identity().is_identity());
+    assert!((g - g).is_identity());
+    assert!(g + -g == Point::identity());
+    assert!(a + -a == Scalar::zero());
+    assert!((a + b) * g == a * g + b * g);
+    assert!((a - b) * g == g * a - g * b);
+    assert!((a * b) * g == a * (b * g));
+    assert!(a.mul_add(&b, &Scalar::one()) == a * b + Scalar::one());
+    assert!(a.mul_sub(&b, &Scalar::one()) == Scalar::one() - a * b);
+    assert!(Point::double_mul_base(&a, &(b * g), &b) == (a * b + b) * g);
+    assert!(Scalar::from(3u64) * g == g + g + g);
+
+    let p = Point::from_bytes(&(a * g).to_bytes()).unwrap();
+    assert!(p == Point::mul_base(&a));
+    assert!(p.is_torsion_free());
+    assert!(Point::from_bytes(&[0xff; 32]).is_none());
+
+    assert!(Scalar::from_canonical_bytes(&CURVE_ORDER).is_none());
+    assert!(Scalar::from_bytes_mod_order(&CURVE_ORDER).is_zero());
+    assert!(Scalar::from_canonical_bytes(&a.to_bytes()) == Some(a));
+    assert!(a * a.invert().unwrap() == Scalar::one());
+    assert!(Scalar::one().invert() == Some(Scalar::one()));
+    assert!(Scalar::zero().invert().is_none());
+
+    let public_key = (a * g).to_bytes();
+    assert!(Point::hash_to_point(&public_key).to_bytes() == EllipticCurveScalar::from_hash(&public_key));
+  }
+
   #[test]
   fn should_test_scalar() {
     let path = PathBuf::from("./tests/tests.txt");
@@ -273,11 +903,10 @@ mod tests {
             &to_fixed_32(prefix_hash),
             &to_fixed_32(image),
             &pubsv,
-            pubs_count,
             &to_fixed_32(secret_key),
             secret_index,
           );
-          assert!(expected == actual);
+          assert!(expected == actual.unwrap().to_bytes());
         }
         "check_ring_signature" => {
           let prefix_hash = hex::decode(split[1]).expect("Error parse prefix hash");
@@ -292,13 +921,15 @@ mod tests {
           }
           let signatures = hex::decode(split[(4 + pubs_count)]).expect("Error parse secret key");
           let expected = split[(5 + pubs_count)] == "true";
-          let actual = Ring::check_signature(
-            &to_fixed_32(prefix_hash),
-            &to_fixed_32(image),
-            &pubsv,
-            pubs_count,
-            &signatures
-          );
+          let actual = match RingSignature::from_bytes(&signatures, pubs_count) {
+            Ok(signature) => Ring::check_signature(
+              &to_fixed_32(prefix_hash),
+              &to_fixed_32(image),
+              &pubsv,
+              &signature,
+            ),
+            Err(_) => false,
+          };
           assert!(expected == actual);
         }
         _ => {}
@@ -0,0 +1,571 @@
+use super::hash::Hash;
+
+const INPUT_GEN_TAG: u8 = 0xff;
+const INPUT_TO_KEY_TAG: u8 = 0x02;
+const OUTPUT_TO_KEY_TAG: u8 = 0x02;
+const OUTPUT_TO_TAGGED_KEY_TAG: u8 = 0x03;
+// rct_signatures type of a coinbase, which carries no ring signatures
+const RCT_TYPE_NULL: u8 = 0;
+
+#[derive(Debug, PartialEq)]
+pub enum SerializationError {
+  UnexpectedEnd,
+  InvalidVarint,
+  InvalidInputType,
+  InvalidOutputType,
+  InvalidRctType,
+  TrailingBytes,
+}
+
+// 7 bits per byte, least significant group first, the high bit set on every
+// byte but the last.
+pub fn write_varint(data: &mut Vec<u8>, mut value: u64) {
+  while value >= 0x80 {
+    data.push((value as u8 & 0x7f) | 0x80);
+    value >>= 7;
+  }
+  data.push(value as u8);
+}
+
+// Reads a varint at *offset and moves past it. Encodings longer than needed
+// or wider than 64 bits are rejected, so every value has one encoding.
+pub fn read_varint(data: &[u8], offset: &mut usize) -> Result<u64, SerializationError> {
+  let mut value: u64 = 0;
+  let mut shift = 0;
+  loop {
+    let byte = *data.get(*offset).ok_or(SerializationError::UnexpectedEnd)?;
+    *offset += 1;
+    let bits = (byte & 0x7f) as u64;
+    if shift == 63 && bits > 1 {
+      return Err(SerializationError::InvalidVarint);
+    }
+    value |= bits << shift;
+    if byte & 0x80 == 0 {
+      if byte == 0 && shift > 0 {
+        return Err(SerializationError::InvalidVarint);
+      }
+      return Ok(value);
+    }
+    shift += 7;
+    if shift > 63 {
+      return Err(SerializationError::InvalidVarint);
+    }
+  }
+}
+
+fn read_bytes<'a>(data: &'a [u8], offset: &mut usize, size: usize) -> Result<&'a [u8], SerializationError> {
+  let end = offset.checked_add(size).ok_or(SerializationError::UnexpectedEnd)?;
+  let bytes = data.get(*offset..end).ok_or(SerializationError::UnexpectedEnd)?;
+  *offset = end;
+  Ok(bytes)
+}
+
+fn read_hash(data: &[u8], offset: &mut usize) -> Result<[u8; 32], SerializationError> {
+  let mut hash: [u8; 32] = [0; 32];
+  hash.copy_from_slice(read_bytes(data, offset, 32)?);
+  Ok(hash)
+}
+
+fn read_u8(data: &[u8], offset: &mut usize) -> Result<u8, SerializationError> {
+  Ok(read_bytes(data, offset, 1)?[0])
+}
+
+// A count read ahead of its items can't claim more items than there are
+// bytes left, each item taking at least one byte.
+fn read_count(data: &[u8], offset: &mut usize) -> Result<usize, SerializationError> {
+  let count = read_varint(data, offset)?;
+  if count > (data.len() - *offset) as u64 {
+    return Err(SerializationError::UnexpectedEnd);
+  }
+  Ok(count as usize)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransactionInput {
+  Gen { height: u64 },
+  ToKey { amount: u64, key_offsets: Vec<u64>, key_image: [u8; 32] },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputTarget {
+  ToKey { key: [u8; 32] },
+  ToTaggedKey { key: [u8; 32], view_tag: u8 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransactionOutput {
+  pub amount: u64,
+  pub target: OutputTarget,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransactionPrefix {
+  pub version: u64,
+  pub unlock_time: u64,
+  pub inputs: Vec<TransactionInput>,
+  pub outputs: Vec<TransactionOutput>,
+  pub extra: Vec<u8>,
+}
+
+impl TransactionPrefix {
+  pub fn write(&self, data: &mut Vec<u8>) {
+    write_varint(data, self.version);
+    write_varint(data, self.unlock_time);
+    write_varint(data, self.inputs.len() as u64);
+    for input in &self.inputs {
+      match input {
+        TransactionInput::Gen { height } => {
+          data.push(INPUT_GEN_TAG);
+          write_varint(data, *height);
+        }
+        TransactionInput::ToKey {
+          amount,
+          key_offsets,
+          key_image,
+        } => {
+          data.push(INPUT_TO_KEY_TAG);
+          write_varint(data, *amount);
+          write_varint(data, key_offsets.len() as u64);
+          for key_offset in key_offsets {
+            write_varint(data, *key_offset);
+          }
+          data.extend_from_slice(key_image);
+        }
+      }
+    }
+    write_varint(data, self.outputs.len() as u64);
+    for output in &self.outputs {
+      write_varint(data, output.amount);
+      match &output.target {
+        OutputTarget::ToKey { key } => {
+          data.push(OUTPUT_TO_KEY_TAG);
+          data.extend_from_slice(key);
+        }
+        OutputTarget::ToTaggedKey { key, view_tag } => {
+          data.push(OUTPUT_TO_TAGGED_KEY_TAG);
+          data.extend_from_slice(key);
+          data.push(*view_tag);
+        }
+      }
+    }
+    write_varint(data, self.extra.len() as u64);
+    data.extend_from_slice(&self.extra);
+  }
+
+  pub fn read(data: &[u8], offset: &mut usize) -> Result<TransactionPrefix, SerializationError> {
+    let version = read_varint(data, offset)?;
+    let unlock_time = read_varint(data, offset)?;
+    let mut inputs = vec![];
+    for _ in 0..read_count(data, offset)? {
+      let input = match read_u8(data, offset)? {
+        INPUT_GEN_TAG => TransactionInput::Gen {
+          height: read_varint(data, offset)?,
+        },
+        INPUT_TO_KEY_TAG => {
+          let amount = read_varint(data, offset)?;
+          let mut key_offsets = vec![];
+          for _ in 0..read_count(data, offset)? {
+            key_offsets.push(read_varint(data, offset)?);
+          }
+          TransactionInput::ToKey {
+            amount,
+            key_offsets,
+            key_image: read_hash(data, offset)?,
+          }
+        }
+        _ => return Err(SerializationError::InvalidInputType),
+      };
+      inputs.push(input);
+    }
+    let mut outputs = vec![];
+    for _ in 0..read_count(data, offset)? {
+      let amount = read_varint(data, offset)?;
+      let target = match read_u8(data, offset)? {
+        OUTPUT_TO_KEY_TAG => OutputTarget::ToKey {
+          key: read_hash(data, offset)?,
+        },
+        OUTPUT_TO_TAGGED_KEY_TAG => OutputTarget::ToTaggedKey {
+          key: read_hash(data, offset)?,
+          view_tag: read_u8(data, offset)?,
+        },
+        _ => return Err(SerializationError::InvalidOutputType),
+      };
+      outputs.push(TransactionOutput { amount, target });
+    }
+    let size = read_count(data, offset)?;
+    let extra = read_bytes(data, offset, size)?.to_vec();
+    Ok(TransactionPrefix {
+      version,
+      unlock_time,
+      inputs,
+      outputs,
+      extra,
+    })
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut data = vec![];
+    self.write(&mut data);
+    data
+  }
+
+  pub fn from_bytes(data: &[u8]) -> Result<TransactionPrefix, SerializationError> {
+    let mut offset = 0;
+    let prefix = TransactionPrefix::read(data, &mut offset)?;
+    if offset != data.len() {
+      return Err(SerializationError::TrailingBytes);
+    }
+    Ok(prefix)
+  }
+
+  // The message signed by the inputs of the transaction.
+  pub fn hash(&self) -> [u8; 32] {
+    Hash::fast(&self.to_bytes())
+  }
+}
+
+// A transaction prefix followed by the signatures (version 1) or the RingCT
+// data (version 2), kept as opaque bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+  pub prefix: TransactionPrefix,
+  pub signatures: Vec<u8>,
+}
+
+impl Transaction {
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut data = self.prefix.to_bytes();
+    data.extend_from_slice(&self.signatures);
+    data
+  }
+
+  // A standalone transaction blob: everything after the prefix is its
+  // signatures.
+  pub fn from_bytes(data: &[u8]) -> Result<Transaction, SerializationError> {
+    let mut offset = 0;
+    let prefix = TransactionPrefix::read(data, &mut offset)?;
+    Ok(Transaction {
+      prefix,
+      signatures: data[offset..].to_vec(),
+    })
+  }
+
+  // The coinbase of a block blob, which has no ring signatures: nothing
+  // follows its prefix in version 1, and a null RingCT type in version 2.
+  pub fn read_coinbase(data: &[u8], offset: &mut usize) -> Result<Transaction, SerializationError> {
+    let prefix = TransactionPrefix::read(data, offset)?;
+    let mut signatures = vec![];
+    if prefix.version >= 2 {
+      let rct_type = read_u8(data, offset)?;
+      if rct_type != RCT_TYPE_NULL {
+        return Err(SerializationError::InvalidRctType);
+      }
+      signatures.push(rct_type);
+    }
+    Ok(Transaction { prefix, signatures })
+  }
+
+  // Version 1 hashes the whole blob. Version 2 hashes the hashes of the
+  // prefix, the RingCT base and the prunable RingCT data, which this crate
+  // only splits for coinbase transactions, where the prunable part is empty.
+  pub fn hash(&self) -> Option<[u8; 32]> {
+    if self.prefix.version < 2 {
+      return Some(Hash::fast(&self.to_bytes()));
+    }
+    if self.signatures != [RCT_TYPE_NULL] {
+      return None;
+    }
+    Some(Transaction::hash_from_parts(
+      &self.prefix.hash(),
+      &Hash::fast(&self.signatures),
+      &[0; 32],
+    ))
+  }
+
+  // H(prefix hash || RingCT base hash || prunable hash), the version 2
+  // transaction hash, the prunable hash being zero when there is none.
+  pub fn hash_from_parts(prefix_hash: &[u8; 32], base_hash: &[u8; 32], prunable_hash: &[u8; 32]) -> [u8; 32] {
+    let mut data = prefix_hash.to_vec();
+    data.extend_from_slice(base_hash);
+    data.extend_from_slice(prunable_hash);
+    Hash::fast(&data)
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockHeader {
+  pub major_version: u8,
+  pub minor_version: u8,
+  pub timestamp: u64,
+  pub prev_id: [u8; 32],
+  pub nonce: u32,
+}
+
+impl BlockHeader {
+  pub fn write(&self, data: &mut Vec<u8>) {
+    write_varint(data, self.major_version as u64);
+    write_varint(data, self.minor_version as u64);
+    write_varint(data, self.timestamp);
+    data.extend_from_slice(&self.prev_id);
+    data.extend_from_slice(&self.nonce.to_le_bytes());
+  }
+
+  pub fn read(data: &[u8], offset: &mut usize) -> Result<BlockHeader, SerializationError> {
+    let major_version = read_varint(data, offset)?;
+    let minor_version = read_varint(data, offset)?;
+    if major_version > u8::MAX as u64 || minor_version > u8::MAX as u64 {
+      return Err(SerializationError::InvalidVarint);
+    }
+    let timestamp = read_varint(data, offset)?;
+    let prev_id = read_hash(data, offset)?;
+    let mut nonce: [u8; 4] = [0; 4];
+    nonce.copy_from_slice(read_bytes(data, offset, 4)?);
+    Ok(BlockHeader {
+      major_version: major_version as u8,
+      minor_version: minor_version as u8,
+      timestamp,
+      prev_id,
+      nonce: u32::from_le_bytes(nonce),
+    })
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+  pub header: BlockHeader,
+  pub miner_tx: Transaction,
+  pub tx_hashes: Vec<[u8; 32]>,
+}
+
+impl Block {
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut data = vec![];
+    self.header.write(&mut data);
+    data.extend_from_slice(&self.miner_tx.to_bytes());
+    write_varint(&mut data, self.tx_hashes.len() as u64);
+    for tx_hash in &self.tx_hashes {
+      data.extend_from_slice(tx_hash);
+    }
+    data
+  }
+
+  pub fn from_bytes(data: &[u8]) -> Result<Block, SerializationError> {
+    let mut offset = 0;
+    let header = BlockHeader::read(data, &mut offset)?;
+    let miner_tx = Transaction::read_coinbase(data, &mut offset)?;
+    let mut tx_hashes = vec![];
+    for _ in 0..read_count(data, &mut offset)? {
+      tx_hashes.push(read_hash(data, &mut offset)?);
+    }
+    if offset != data.len() {
+      return Err(SerializationError::TrailingBytes);
+    }
+    Ok(Block {
+      header,
+      miner_tx,
+      tx_hashes,
+    })
+  }
+
+  // Tree hash of the coinbase hash followed by the other transaction hashes.
+  pub fn merkle_root(&self) -> Option<[u8; 32]> {
+    let mut hashes = vec![self.miner_tx.hash()?];
+    hashes.extend_from_slice(&self.tx_hashes);
+    Hash::tree(&hashes)
+  }
+
+  // header || merkle root || varint(transaction count), the input of both
+  // the block ID and the proof of work hash.
+  pub fn hashing_blob(&self) -> Option<Vec<u8>> {
+    let mut data = vec![];
+    self.header.write(&mut data);
+    data.extend_from_slice(&self.merkle_root()?);
+    write_varint(&mut data, self.tx_hashes.len() as u64 + 1);
+    Some(data)
+  }
+
+  // H(varint(size) || hashing blob)
+  pub fn id(&self) -> Option<[u8; 32]> {
+    let blob = self.hashing_blob()?;
+    let mut data = vec![];
+    write_varint(&mut data, blob.len() as u64);
+    data.extend_from_slice(&blob);
+    Some(Hash::fast(&data))
+  }
+
+  // The cn_slow_hash variant of a major version: the original one, variant
+  // 1 from version 7 and variant 2 from version 8. Later versions switched to
+  // proofs of work this crate doesn't implement.
+  pub fn pow_variant(major_version: u8) -> Option<usize> {
+    match major_version {
+      0..=6 => Some(0),
+      7 => Some(1),
+      8 | 9 => Some(2),
+      _ => None,
+    }
+  }
+
+  pub fn pow_hash(&self) -> Option<[u8; 32]> {
+    let variant = Block::pow_variant(self.header.major_version)?;
+    Some(Hash::slow_with_variant(&self.hashing_blob()?, variant))
+  }
+
+  // False as well when the proof of work hash can't be computed.
+  pub fn check_pow(&self, difficulty: u64) -> bool {
+    match self.pow_hash() {
+      Some(hash) => Hash::check_with_difficulty(&hash, difficulty),
+      None => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const GENESIS_TX: &str = concat!(
+    "013c01ff0001ffffffffffff03029b2e4c0281c0b02e7c53291a94d1d0cbff8883f8024f5142ee494ffbbd08807121",
+    "017767aafcde9be00dcfd098715ebcf7f410daebc582fda69d24a28e9d0bc890d1"
+  );
+
+  #[test]
+  fn should_encode_varints() {
+    let vectors: [(u64, &str); 6] = [
+      (0, "00"),
+      (1, "01"),
+      (127, "7f"),
+      (128, "8001"),
+      (300, "ac02"),
+      (u64::MAX, "ffffffffffffffffff01"),
+    ];
+    for (value, encoded) in vectors.iter() {
+      let mut data = vec![];
+      write_varint(&mut data, *value);
+      assert!(hex::encode(&data) == *encoded);
+      let mut offset = 0;
+      assert!(read_varint(&data, &mut offset) == Ok(*value));
+      assert!(offset == data.len());
+    }
+    for invalid in ["80", "8000", "ffffffffffffffffff02", "ffffffffffffffffff8001"].iter() {
+      let data = hex::decode(invalid).unwrap();
+      assert!(read_varint(&data, &mut 0).is_err());
+    }
+  }
+
+  #[test]
+  fn should_parse_transaction_prefix() {
+    // the genesis transaction of Hash::fast's test
+    let blob = hex::decode(concat!(
+      "013c01ff000101029b2e4c0281c0b02e7c53291a94d1d0cbff8883f8024f5142ee494ffbbd08807121",
+      "01a9a4569f7e10164a32324b2b878ae32d98be0949ce6e0150ba1d7e54d60969e5"
+    ))
+    .unwrap();
+    let prefix = TransactionPrefix::from_bytes(&blob).unwrap();
+    assert!(prefix.version == 1 && prefix.unlock_time == 60);
+    assert!(prefix.inputs == vec![TransactionInput::Gen { height: 0 }]);
+    assert!(prefix.outputs.len() == 1 && prefix.outputs[0].amount == 1);
+    assert!(prefix.extra.len() == 33 && prefix.extra[0] == 1);
+    assert!(prefix.to_bytes() == blob);
+    assert!(
+      Transaction::from_bytes(&blob).unwrap().hash().unwrap()
+        == [
+          81, 131, 30, 137, 17, 68, 149, 122, 23, 4, 105, 195, 35, 123, 221, 255, 230, 192, 96, 73,
+          129, 38, 117, 210, 237, 178, 168, 52, 82, 247, 162, 80
+        ]
+    );
+    assert!(TransactionPrefix::from_bytes(&blob[..blob.len() - 1]) == Err(SerializationError::UnexpectedEnd));
+    let mut trailing = blob.clone();
+    trailing.push(0);
+    assert!(TransactionPrefix::from_bytes(&trailing) == Err(SerializationError::TrailingBytes));
+  }
+
+  #[test]
+  fn should_round_trip_transaction_prefix() {
+    let prefix = TransactionPrefix {
+      version: 2,
+      unlock_time: 0,
+      inputs: vec![TransactionInput::ToKey {
+        amount: 0,
+        key_offsets: vec![1_000_000, 20, 3],
+        key_image: [7; 32],
+      }],
+      outputs: vec![
+        TransactionOutput {
+          amount: 0,
+          target: OutputTarget::ToKey { key: [8; 32] },
+        },
+        TransactionOutput {
+          amount: 0,
+          target: OutputTarget::ToTaggedKey {
+            key: [9; 32],
+            view_tag: 0xab,
+          },
+        },
+      ],
+      extra: vec![2, 9, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+    };
+    let blob = prefix.to_bytes();
+    assert!(TransactionPrefix::from_bytes(&blob) == Ok(prefix));
+    let mut unknown = blob.clone();
+    unknown[3] = 0x01;
+    assert!(TransactionPrefix::from_bytes(&unknown) == Err(SerializationError::InvalidInputType));
+  }
+
+  #[test]
+  fn should_hash_genesis_block() {
+    let blob = hex::decode(format!(
+      "010000{}10270000{}00",
+      "00".repeat(32),
+      GENESIS_TX
+    ))
+    .unwrap();
+    let block = Block::from_bytes(&blob).unwrap();
+    assert!(block.header.major_version == 1 && block.header.nonce == 10000);
+    assert!(block.miner_tx.prefix.inputs == vec![TransactionInput::Gen { height: 0 }]);
+    assert!(block.tx_hashes.is_empty());
+    assert!(block.to_bytes() == blob);
+    assert!(hex::encode(block.id().unwrap()) == "418015bb9ae982a1975da7d79277c2705727a56894ba0fb246adaabb1f4632e3");
+    assert!(Block::from_bytes(&blob[..blob.len() - 1]).is_err());
+  }
+
+  #[test]
+  fn should_hash_version_2_coinbase() {
+    let mut block = Block::from_bytes(
+      &hex::decode(format!("010000{}10270000{}00", "00".repeat(32), GENESIS_TX)).unwrap(),
+    )
+    .unwrap();
+    block.miner_tx.prefix.version = 2;
+    block.miner_tx.signatures = vec![0];
+    block.tx_hashes = vec![[1; 32], [2; 32]];
+    let tx_hash = Transaction::hash_from_parts(&block.miner_tx.prefix.hash(), &Hash::fast(&[0]), &[0; 32]);
+    assert!(block.miner_tx.hash() == Some(tx_hash));
+    assert!(block.merkle_root() == Hash::tree(&[tx_hash, [1; 32], [2; 32]]));
+    assert!(Block::from_bytes(&block.to_bytes()) == Ok(block.clone()));
+
+    block.miner_tx.signatures = vec![5];
+    assert!(block.miner_tx.hash().is_none() && block.id().is_none());
+    assert!(Block::from_bytes(&block.to_bytes()) == Err(SerializationError::InvalidRctType));
+  }
+
+  #[test]
+  fn should_hash_proof_of_work() {
+    let mut block = Block::from_bytes(
+      &hex::decode(format!("010000{}10270000{}00", "00".repeat(32), GENESIS_TX)).unwrap(),
+    )
+    .unwrap();
+    let blob = block.hashing_blob().unwrap();
+    assert!(blob.len() == 3 + 32 + 4 + 32 + 1 && blob[blob.len() - 1] == 1);
+    assert!(block.pow_hash() == Some(Hash::slow(&blob)));
+    assert!(block.check_pow(1));
+    assert!(!block.check_pow(u64::MAX));
+
+    block.header.major_version = 8;
+    let blob = block.hashing_blob().unwrap();
+    assert!(block.pow_hash() == Some(Hash::slow_with_variant(&blob, 2)));
+    assert!(block.pow_hash() != Some(Hash::slow(&blob)));
+
+    assert!(Block::pow_variant(7) == Some(1));
+    block.header.major_version = 12;
+    assert!(block.pow_hash().is_none() && !block.check_pow(1));
+  }
+}
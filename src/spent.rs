@@ -0,0 +1,227 @@
+use super::key::KeyImage;
+use super::serialization::{read_varint, write_varint};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+#[derive(Debug, PartialEq)]
+pub enum SpentError {
+  // the first image of the batch already spent, or spent twice in the batch
+  DoubleSpend([u8; 32]),
+  InvalidKeyImage,
+  InvalidHeight,
+  InvalidEncoding,
+}
+
+// Key images spent on chain with the height of the block spending them, so
+// that the images of popped blocks can be released.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyImageSet {
+  heights: HashMap<[u8; 32], u64>,
+  blocks: BTreeMap<u64, Vec<[u8; 32]>>,
+}
+
+impl KeyImageSet {
+  pub fn new() -> KeyImageSet {
+    KeyImageSet::default()
+  }
+
+  pub fn contains(&self, image: &[u8; 32]) -> bool {
+    self.heights.contains_key(image)
+  }
+
+  // Height of the block which spent the image.
+  pub fn height(&self, image: &[u8; 32]) -> Option<u64> {
+    self.heights.get(image).copied()
+  }
+
+  pub fn top_height(&self) -> Option<u64> {
+    self.blocks.keys().next_back().copied()
+  }
+
+  pub fn len(&self) -> usize {
+    self.heights.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.heights.is_empty()
+  }
+
+  // The first image of the batch which is already spent, or repeated within
+  // the batch, without inserting anything.
+  pub fn check_batch(&self, images: &[[u8; 32]]) -> Result<(), SpentError> {
+    for (i, image) in images.iter().enumerate() {
+      if !KeyImage::is_valid(image) {
+        return Err(SpentError::InvalidKeyImage);
+      }
+      if self.contains(image) || images[..i].contains(image) {
+        return Err(SpentError::DoubleSpend(*image));
+      }
+    }
+    Ok(())
+  }
+
+  // Inserts all the images spent by a block, or none of them. Blocks are
+  // added in chain order, a height below the top one is refused.
+  pub fn insert_batch(&mut self, height: u64, images: &[[u8; 32]]) -> Result<(), SpentError> {
+    if self.top_height().is_some_and(|top| height < top) {
+      return Err(SpentError::InvalidHeight);
+    }
+    self.check_batch(images)?;
+    for image in images {
+      self.heights.insert(*image, height);
+    }
+    self.blocks.entry(height).or_default().extend_from_slice(images);
+    Ok(())
+  }
+
+  // Releases the images spent above height, when blocks are popped on a
+  // reorganisation. Returns them in the order they were inserted.
+  pub fn rollback(&mut self, height: u64) -> Vec<[u8; 32]> {
+    let popped = match height.checked_add(1) {
+      Some(above) => self.blocks.split_off(&above),
+      None => return vec![],
+    };
+    let mut released = vec![];
+    for images in popped.into_values() {
+      for image in &images {
+        self.heights.remove(image);
+      }
+      released.extend(images);
+    }
+    released
+  }
+
+  // varint(block count) then varint(height) || varint(count) || images for
+  // every block, by increasing height.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut data = vec![];
+    write_varint(&mut data, self.blocks.len() as u64);
+    for (height, images) in &self.blocks {
+      write_varint(&mut data, *height);
+      write_varint(&mut data, images.len() as u64);
+      for image in images {
+        data.extend_from_slice(image);
+      }
+    }
+    data
+  }
+
+  pub fn from_bytes(data: &[u8]) -> Result<KeyImageSet, SpentError> {
+    let mut set = KeyImageSet::new();
+    let mut offset = 0;
+    let varint = |offset: &mut usize| read_varint(data, offset).map_err(|_| SpentError::InvalidEncoding);
+    let blocks = varint(&mut offset)?;
+    for _ in 0..blocks {
+      let height = varint(&mut offset)?;
+      let count = varint(&mut offset)?;
+      if set.top_height().is_some_and(|top| height <= top) || count > ((data.len() - offset) / 32) as u64 {
+        return Err(SpentError::InvalidEncoding);
+      }
+      let images: Vec<[u8; 32]> = data[offset..offset + count as usize * 32]
+        .chunks(32)
+        .map(|chunk| {
+          let mut image: [u8; 32] = [0; 32];
+          image.copy_from_slice(chunk);
+          image
+        })
+        .collect();
+      offset += images.len() * 32;
+      set.insert_batch(height, &images)?;
+    }
+    if offset != data.len() {
+      return Err(SpentError::InvalidEncoding);
+    }
+    Ok(set)
+  }
+
+  // Writes and syncs a temporary file first, then renames it over path, so a
+  // crash never leaves a truncated set behind.
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let mut file = fs::File::create(&temporary)?;
+    file.write_all(&self.to_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+  }
+
+  pub fn load<P: AsRef<Path>>(path: P) -> io::Result<KeyImageSet> {
+    let data = fs::read(path)?;
+    KeyImageSet::from_bytes(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::key::Key;
+  use super::*;
+
+  fn images(count: usize) -> Vec<[u8; 32]> {
+    (0..count)
+      .map(|_| {
+        let mut public_key: [u8; 32] = [0; 32];
+        let mut secret_key: [u8; 32] = [0; 32];
+        Key::generate_key_pair(&mut public_key, &mut secret_key);
+        Key::generate_key_image(&public_key, &secret_key)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn should_detect_double_spends() {
+    let images = images(5);
+    let mut set = KeyImageSet::new();
+    assert!(set.insert_batch(10, &images[..2]).is_ok());
+    assert!(set.insert_batch(10, &images[2..3]).is_ok());
+    assert!(set.len() == 3 && set.height(&images[2]) == Some(10));
+
+    // nothing of a rejected batch is inserted
+    assert!(set.insert_batch(11, &[images[3], images[1]]) == Err(SpentError::DoubleSpend(images[1])));
+    assert!(set.insert_batch(11, &[images[3], images[3]]) == Err(SpentError::DoubleSpend(images[3])));
+    assert!(!set.contains(&images[3]) && set.len() == 3);
+
+    assert!(set.insert_batch(9, &images[3..4]) == Err(SpentError::InvalidHeight));
+    assert!(set.insert_batch(11, &[[0; 32]]) == Err(SpentError::InvalidKeyImage));
+    assert!(set.insert_batch(11, &images[3..]).is_ok());
+    assert!(set.top_height() == Some(11));
+  }
+
+  #[test]
+  fn should_rollback_to_height() {
+    let images = images(4);
+    let mut set = KeyImageSet::new();
+    for (height, image) in images.iter().enumerate() {
+      set.insert_batch(height as u64 + 100, &[*image]).unwrap();
+    }
+    assert!(set.rollback(101) == vec![images[2], images[3]]);
+    assert!(set.top_height() == Some(101) && set.len() == 2);
+    assert!(!set.contains(&images[2]) && set.contains(&images[1]));
+    // the released images can be spent again on the new chain
+    assert!(set.insert_batch(102, &images[3..]).is_ok());
+    assert!(set.rollback(200).is_empty() && set.rollback(u64::MAX).is_empty());
+    set.rollback(0);
+    assert!(set.is_empty() && set.top_height().is_none());
+  }
+
+  #[test]
+  fn should_save_and_load() {
+    let images = images(3);
+    let mut set = KeyImageSet::new();
+    set.insert_batch(1, &images[..2]).unwrap();
+    set.insert_batch(5, &images[2..]).unwrap();
+    let data = set.to_bytes();
+    assert!(data.len() == 1 + 2 + 2 * 32 + 2 + 32);
+    assert!(KeyImageSet::from_bytes(&data) == Ok(set.clone()));
+    assert!(KeyImageSet::from_bytes(&data[..data.len() - 1]) == Err(SpentError::InvalidEncoding));
+
+    let path = std::env::temp_dir().join(format!("key_images_{}.bin", std::process::id()));
+    set.save(&path).unwrap();
+    let loaded = KeyImageSet::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(loaded == set);
+    assert!(KeyImageSet::load(&path).is_err());
+  }
+}
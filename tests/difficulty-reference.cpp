@@ -0,0 +1,181 @@
+// Computes the difficulties of tests/difficulty-lwma1.txt,
+// tests/difficulty-lwma3.txt and tests/difficulty-zawy.txt from their
+// timestamps, simulated block times around the 120 second target.
+//
+// LWMA1_ and LWMA3_ are the bodies of zawy12's reference implementations
+// from https://github.com/zawy12/difficulty-algorithms, unmodified. Their
+// hard coded guess after a fork is left to the driver, which returns it
+// until N + 1 blocks exist.
+//
+// Zawy v1 is CryptoNote's simple moving average with a window of 17 blocks
+// and no cut or lag (zawy12/difficulty-algorithms issue 1). next_difficulty
+// is the CryptoNote reference with those constants.
+//
+//   g++ -O2 -o difficulty-reference tests/difficulty-reference.cpp
+//   ./difficulty-reference lwma1 < tests/difficulty-lwma1.txt
+
+#include <algorithm>
+#include <cassert>
+#include <cstdint>
+#include <cstring>
+#include <iostream>
+#include <vector>
+
+typedef uint64_t difficulty_type;
+
+difficulty_type LWMA1_(std::vector<uint64_t> timestamps, std::vector<uint64_t> cumulative_difficulties, uint64_t T,
+                       uint64_t N) {
+  uint64_t L(0), next_D, i, this_timestamp(0), previous_timestamp(0), avg_D;
+
+  previous_timestamp = timestamps[0] - T;
+  for (i = 1; i <= N; i++) {
+    // Safely prevent out-of-sequence timestamps
+    if (timestamps[i] > previous_timestamp) {
+      this_timestamp = timestamps[i];
+    } else {
+      this_timestamp = previous_timestamp + 1;
+    }
+    L += i * std::min(6 * T, this_timestamp - previous_timestamp);
+    previous_timestamp = this_timestamp;
+  }
+  if (L < N * N * T / 20) {
+    L = N * N * T / 20;
+  }
+  avg_D = (cumulative_difficulties[N] - cumulative_difficulties[0]) / N;
+
+  // Prevent round off error for small D and overflow for large D.
+  if (avg_D > 2000000 * N * N * T) {
+    next_D = (avg_D / (200 * L)) * (N * (N + 1) * T * 99);
+  } else {
+    next_D = (avg_D * N * (N + 1) * T * 99) / (200 * L);
+  }
+
+  // Optional. Make all insignificant digits zero for easy reading.
+  i = 1000000000;
+  while (i > 1) {
+    if (next_D > i * 100) {
+      next_D = ((next_D + i / 2) / i) * i;
+      break;
+    } else {
+      i /= 10;
+    }
+  }
+  return next_D;
+}
+
+difficulty_type LWMA3_(std::vector<uint64_t> timestamps, std::vector<difficulty_type> cumulative_difficulties,
+                       uint64_t T, uint64_t N) {
+  uint64_t L(0), ST, sum_3_ST(0), next_D, prev_D, this_timestamp, previous_timestamp;
+
+  previous_timestamp = timestamps[0];
+  for (uint64_t i = 1; i <= N; i++) {
+    if (timestamps[i] > previous_timestamp) {
+      this_timestamp = timestamps[i];
+    } else {
+      this_timestamp = previous_timestamp + 1;
+    }
+    ST = std::min(6 * T, this_timestamp - previous_timestamp);
+    previous_timestamp = this_timestamp;
+    L += ST * i;
+    if (i > N - 3) {
+      sum_3_ST += ST;
+    }
+  }
+  next_D = ((cumulative_difficulties[N] - cumulative_difficulties[0]) * T * (N + 1) * 99) / (100 * 2 * L);
+  prev_D = cumulative_difficulties[N] - cumulative_difficulties[N - 1];
+  next_D = std::max((prev_D * 67) / 100, std::min(next_D, (prev_D * 150) / 100));
+  if (sum_3_ST < (8 * T) / 10) {
+    next_D = std::max(next_D, (prev_D * 108) / 100);
+  }
+  return next_D;
+}
+
+const size_t DIFFICULTY_WINDOW = 17;
+const size_t DIFFICULTY_CUT = 0;
+const size_t DIFFICULTY_LAG = 0;
+
+static inline void mul(uint64_t a, uint64_t b, uint64_t &low, uint64_t &high) {
+  unsigned __int128 res = (unsigned __int128)a * b;
+  low = (uint64_t)res;
+  high = (uint64_t)(res >> 64);
+}
+
+difficulty_type next_difficulty(std::vector<uint64_t> timestamps, std::vector<difficulty_type> cumulative_difficulties,
+                                size_t target_seconds) {
+  if (timestamps.size() > DIFFICULTY_WINDOW) {
+    timestamps.resize(DIFFICULTY_WINDOW);
+    cumulative_difficulties.resize(DIFFICULTY_WINDOW);
+  }
+
+  size_t length = timestamps.size();
+  assert(length == cumulative_difficulties.size());
+  if (length <= 1) {
+    return 1;
+  }
+  static_assert(DIFFICULTY_WINDOW >= 2, "Window is too small");
+  assert(length <= DIFFICULTY_WINDOW);
+  sort(timestamps.begin(), timestamps.end());
+  size_t cut_begin, cut_end;
+  static_assert(2 * DIFFICULTY_CUT <= DIFFICULTY_WINDOW - 2, "Cut length is too large");
+  if (length <= DIFFICULTY_WINDOW - 2 * DIFFICULTY_CUT) {
+    cut_begin = 0;
+    cut_end = length;
+  } else {
+    cut_begin = (length - (DIFFICULTY_WINDOW - 2 * DIFFICULTY_CUT) + 1) / 2;
+    cut_end = cut_begin + (DIFFICULTY_WINDOW - 2 * DIFFICULTY_CUT);
+  }
+  assert(/*cut_begin >= 0 &&*/ cut_begin + 2 <= cut_end && cut_end <= length);
+  uint64_t time_span = timestamps[cut_end - 1] - timestamps[cut_begin];
+  if (time_span == 0) {
+    time_span = 1;
+  }
+  difficulty_type total_work = cumulative_difficulties[cut_end - 1] - cumulative_difficulties[cut_begin];
+  assert(total_work > 0);
+  uint64_t low, high;
+  mul(total_work, target_seconds, low, high);
+  if (high != 0 || low + time_span - 1 < low) {
+    return 0;
+  }
+  return (low + time_span - 1) / time_span;
+}
+
+int main(int argc, char **argv) {
+  if (argc != 2) {
+    std::cerr << "usage: " << argv[0] << " lwma1|lwma3|zawy < timestamps" << std::endl;
+    return 1;
+  }
+  const uint64_t T = 120;
+  uint64_t N, guess = 1000;
+  if (!strcmp(argv[1], "lwma1")) {
+    N = 90;
+  } else if (!strcmp(argv[1], "lwma3")) {
+    N = 60;
+  } else if (!strcmp(argv[1], "zawy")) {
+    N = DIFFICULTY_WINDOW + DIFFICULTY_LAG - 1;
+  } else {
+    std::cerr << "unknown algorithm " << argv[1] << std::endl;
+    return 1;
+  }
+
+  std::vector<uint64_t> timestamps, cumulative_difficulties;
+  uint64_t timestamp, ignored;
+  while (std::cin >> timestamp >> ignored) {
+    size_t begin = timestamps.size() > N + 1 ? timestamps.size() - (N + 1) : 0;
+    std::vector<uint64_t> ts(timestamps.begin() + begin, timestamps.end());
+    std::vector<uint64_t> cd(cumulative_difficulties.begin() + begin, cumulative_difficulties.end());
+    difficulty_type next;
+    if (!strcmp(argv[1], "zawy")) {
+      next = next_difficulty(ts, cd, T);
+    } else if (ts.size() < N + 1) {
+      next = guess;
+    } else if (!strcmp(argv[1], "lwma1")) {
+      next = LWMA1_(ts, cd, T, N);
+    } else {
+      next = LWMA3_(ts, cd, T, N);
+    }
+    std::cout << timestamp << " " << next << std::endl;
+    timestamps.push_back(timestamp);
+    cumulative_difficulties.push_back((cumulative_difficulties.empty() ? 0 : cumulative_difficulties.back()) + next);
+  }
+  return 0;
+}